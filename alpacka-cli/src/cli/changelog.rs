@@ -0,0 +1,151 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::Path,
+};
+
+use alpacka::{
+    changelog::{changelog as plugin_changelogs, date},
+    condition::ConditionContext,
+    manifest::{GenerationHash, GenerationsFile},
+    smith::{enums::Loaders, Git},
+};
+use error_stack::{Context, IntoReport, Report, Result, ResultExt};
+
+use crate::cli::{install::load_config, peek_generations_file, short};
+
+use super::clap::ListGenerationsFormatMethod;
+
+#[derive(Debug)]
+pub enum Error {
+    Load,
+    NoGeneration,
+    UnknownGeneration(u64),
+    Changelog,
+    Format,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Load => f.write_str("Failed to load generation"),
+            Self::NoGeneration => f.write_str(
+                "No generation exists for the current config, run `alpacka install` first",
+            ),
+            Self::UnknownGeneration(id) => write!(f, "Generation {id} does not exist"),
+            Self::Changelog => f.write_str("Failed to create changelog"),
+            Self::Format => f.write_str("Failed to format output"),
+        }
+    }
+}
+
+impl Context for Error {}
+
+/// Show the commits of plugins between two generations, given by their IDs
+///
+/// `to` defaults to the latest generation of the current config, and `from` to the generation of the same config before
+/// `to`.
+///
+/// # Errors
+/// Errors if the config or generations file cannot be loaded, if a generation does not exist, or if the commits of a
+/// plugin cannot be read.
+pub fn changelog(
+    config_path: &Path,
+    data_path: &Path,
+    profile: Option<&str>,
+    from: Option<u64>,
+    to: Option<u64>,
+    names: &[String],
+    format_style: &ListGenerationsFormatMethod,
+) -> Result<(), Error> {
+    let config = load_config(config_path, profile).change_context(Error::Load)?;
+    let conditions = config.evaluate_conditions(&ConditionContext::detect());
+    let config_hash = config.stable_hash(&conditions);
+
+    let generations_path = data_path.join("generations.rkyv");
+    let generations_file =
+        peek_generations_file(&generations_path, Some((config.legacy_hash(), config_hash)))
+            .change_context(Error::Load)?;
+
+    let generations = GenerationsFile::from_bytes(&generations_file)
+        .attach_printable_lazy(|| format!("Generations file path: {}", generations_path.display()))
+        .change_context(Error::Load)?;
+
+    let by_id = |id| -> Result<GenerationHash, Error> {
+        generations
+            .get_by_id(id)
+            .map(|(hash, _)| *hash)
+            .ok_or_else(|| Report::new(Error::UnknownGeneration(id)))
+    };
+
+    let to = match to {
+        Some(to) => by_id(to)?,
+        None => generations
+            .get_latest_generation_number(config_hash)
+            .map(|generation| GenerationHash(config_hash, generation))
+            .ok_or_else(|| Report::new(Error::NoGeneration))?,
+    };
+
+    let Some(from) = from
+        .map(by_id)
+        .transpose()?
+        .or_else(|| generations.get_previous(to))
+    else {
+        match format_style {
+            ListGenerationsFormatMethod::Human => println!(
+                "Generation {} is the first generation of its config, there is nothing to compare it to",
+                generations.generations[&to].id
+            ),
+            ListGenerationsFormatMethod::Json => println!("[]"),
+        }
+
+        return Ok(());
+    };
+
+    let (from, to) = (
+        &generations.generations[&from],
+        &generations.generations[&to],
+    );
+
+    let smiths: Vec<Loaders> = vec![Loaders::Git(Git::new())];
+    let changelogs = plugin_changelogs(&from.manifest, &to.manifest, &smiths, data_path, names)
+        .change_context(Error::Changelog)?;
+
+    match format_style {
+        ListGenerationsFormatMethod::Human => {
+            if changelogs.is_empty() {
+                println!(
+                    "No plugins changed between generations {} and {}",
+                    from.id, to.id
+                );
+            }
+
+            for changelog in changelogs {
+                println!(
+                    "{} {}..{}",
+                    changelog.name,
+                    short(&changelog.from),
+                    short(&changelog.to)
+                );
+
+                for commit in changelog.commits {
+                    println!(
+                        "  {} {} {}: {}",
+                        short(&commit.id),
+                        date(commit.time),
+                        commit.author,
+                        commit.subject
+                    );
+                }
+            }
+        }
+        ListGenerationsFormatMethod::Json => {
+            let json = serde_json::to_string(&changelogs)
+                .into_report()
+                .change_context(Error::Format)?;
+
+            println!("{json}");
+        }
+    }
+
+    Ok(())
+}
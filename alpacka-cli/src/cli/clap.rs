@@ -1,20 +1,29 @@
 use std::path::PathBuf;
 
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 
 /// Alpacka: the next-generation package manager for Neovim.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub enum Cli {
     Install {
-        /// The path to the config file
-        /// Defaults to `$XDG_CONFIG_HOME/nvim/packages.json`
+        /// The path to the config file. Files ending in `.lua` are evaluated as Lua
+        /// Defaults to `$XDG_CONFIG_HOME/nvim/packages.json`, or `packages.lua` if it does not exist
         #[arg(short, long)]
         path: Option<PathBuf>,
         /// The data directory
         /// Defaults to `$XDG_DATA_HOME/nvim/site/pack/alpacka`
         #[arg(short, long)]
         data_dir: Option<PathBuf>,
+        /// The profile to install. Each profile is installed to `{data_dir}-{profile}`, next to the data directory
+        #[arg(long)]
+        profile: Option<String>,
+        /// A description of the generation, recorded if a new generation is created
+        #[arg(short, long)]
+        message: Option<String>,
+        /// Resolve the packages and print what installing would change, without writing anything
+        #[arg(long)]
+        dry_run: bool,
     },
     ListGenerations {
         /// The data directory containing the generations.rkyv file
@@ -25,13 +34,255 @@ pub enum Cli {
         /// Defaults to `ListGenerationsFormatMethod::Human`
         #[arg(short, long)]
         format_style: Option<ListGenerationsFormatMethod>,
+        /// List the generations of a profile instead
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Resolve the packages which follow a branch again, and install them as a new generation of the current config
+    Update {
+        /// The packages to update, as named in the config or installed
+        /// Defaults to every package which follows a branch
+        names: Vec<String>,
+        /// The path to the config file
+        /// Defaults to `$XDG_CONFIG_HOME/nvim/packages.json`, or `packages.lua` if it does not exist
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// The data directory
+        /// Defaults to `$XDG_DATA_HOME/nvim/site/pack/alpacka`
+        #[arg(short, long)]
+        data_dir: Option<PathBuf>,
+        /// Update the packages of a profile instead
+        #[arg(long)]
+        profile: Option<String>,
+        /// Update packages with breaking changes too, instead of holding them back
+        #[arg(long)]
+        accept_breaking: bool,
+        /// Resolve the packages and print what updating would change, without writing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// The output format
+        /// Defaults to `ListGenerationsFormatMethod::Human`
+        #[arg(short, long)]
+        format_style: Option<ListGenerationsFormatMethod>,
+    },
+    /// Check out a previous generation of the current config, without resolving any package
+    Rollback {
+        /// The ID of the generation to roll back to, as shown by `list-generations`
+        /// This can be a generation of another config, whose config then has to be restored with
+        /// `alpacka generation restore-config` for `install` to keep it
+        /// Defaults to the generation before the active one
+        #[arg(long)]
+        to: Option<u64>,
+        /// The path to the config file
+        /// Defaults to `$XDG_CONFIG_HOME/nvim/packages.json`, or `packages.lua` if it does not exist
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// The data directory
+        /// Defaults to `$XDG_DATA_HOME/nvim/site/pack/alpacka`
+        #[arg(short, long)]
+        data_dir: Option<PathBuf>,
+        /// Roll back the generation of a profile instead
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Check the packages of the current config against their remotes, without installing anything
+    Outdated {
+        /// The path to the config file
+        /// Defaults to `$XDG_CONFIG_HOME/nvim/packages.json`, or `packages.lua` if it does not exist
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// The data directory
+        /// Defaults to `$XDG_DATA_HOME/nvim/site/pack/alpacka`
+        #[arg(short, long)]
+        data_dir: Option<PathBuf>,
+        /// The output format
+        /// Defaults to `ListGenerationsFormatMethod::Human`
+        #[arg(short, long)]
+        format_style: Option<ListGenerationsFormatMethod>,
+        /// Check the packages of a profile instead
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Show the commits of the packages which changed between two generations, by default the latest two of the current config
+    Changelog {
+        /// The packages to show, as named in the config or installed
+        /// Defaults to every package which changed
+        names: Vec<String>,
+        /// The ID of the older generation, as shown by `list-generations`
+        /// Defaults to the generation of the same config before `--to`
+        #[arg(long)]
+        from: Option<u64>,
+        /// The ID of the newer generation
+        /// Defaults to the latest generation of the current config
+        #[arg(long)]
+        to: Option<u64>,
+        /// The path to the config file
+        /// Defaults to `$XDG_CONFIG_HOME/nvim/packages.json`, or `packages.lua` if it does not exist
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// The data directory
+        /// Defaults to `$XDG_DATA_HOME/nvim/site/pack/alpacka`
+        #[arg(short, long)]
+        data_dir: Option<PathBuf>,
+        /// The output format
+        /// Defaults to `ListGenerationsFormatMethod::Human`
+        #[arg(short, long)]
+        format_style: Option<ListGenerationsFormatMethod>,
+        /// Show the changes of a profile instead
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Show what changed between two generations of any config
+    Diff {
+        /// The ID of the older generation, as shown by `list-generations`
+        from: u64,
+        /// The ID of the newer generation
+        to: u64,
+        /// The data directory
+        /// Defaults to `$XDG_DATA_HOME/nvim/site/pack/alpacka`
+        #[arg(short, long)]
+        data_dir: Option<PathBuf>,
+        /// The output format
+        /// Defaults to `ListGenerationsFormatMethod::Human`
+        #[arg(short, long)]
+        format_style: Option<ListGenerationsFormatMethod>,
+        /// Compare generations of a profile instead
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Inspect or restore a generation of any config
+    Generation {
+        #[command(subcommand)]
+        command: GenerationCommand,
+    },
+    /// Add a package to the config file
+    Add {
+        /// The name of the package, e.g. `github:nvim-lua/plenary.nvim`
+        name: String,
+        /// The package version, in the form `{prefix}:{value}`
+        #[arg(long)]
+        version: Option<String>,
+        /// Don't load the package on startup
+        #[arg(long)]
+        optional: bool,
+        /// The path to the config file. Only JSON config files can be edited
+        /// Defaults to `$XDG_CONFIG_HOME/nvim/packages.json`
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+    },
+    /// Remove a package from the config file
+    Remove {
+        /// The name of the package
+        name: String,
+        /// The path to the config file. Only JSON config files can be edited
+        /// Defaults to `$XDG_CONFIG_HOME/nvim/packages.json`
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+    },
+    /// Import the plugins of another package manager into a new config, pinned to their current commits
+    Import {
+        /// The package manager to import from
+        format: ImportFormatMethod,
+        /// The files to import, e.g. `lazy-lock.json`, a snapshot file, or an `init.vim` with `Plug` lines
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// The directories containing the plugins' local checkouts, used to find their repositories
+        /// Defaults to the directories the package manager installs plugins to
+        #[arg(long)]
+        plugin_dir: Vec<PathBuf>,
+        /// The path to write the config file to. This file must not exist yet
+        /// Defaults to `$XDG_CONFIG_HOME/nvim/packages.json`
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// The data directory
+        /// Defaults to `$XDG_DATA_HOME/nvim/site/pack/alpacka`
+        #[arg(short, long)]
+        data_dir: Option<PathBuf>,
+    },
+    /// Export the generation of the current config to the lockfile format of another tool
+    Export {
+        /// The format to export to
+        format: ExportFormatMethod,
+        /// The file to write the lockfile to
+        /// Defaults to printing the lockfile to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// The path to the config file
+        /// Defaults to `$XDG_CONFIG_HOME/nvim/packages.json`, or `packages.lua` if it does not exist
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// The data directory
+        /// Defaults to `$XDG_DATA_HOME/nvim/site/pack/alpacka`
+        #[arg(short, long)]
+        data_dir: Option<PathBuf>,
+        /// Export the generation of a profile instead
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    Schema {
+        /// The file to write the JSON schema to
+        /// Defaults to printing the schema to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
 }
 
-#[derive(Debug, ValueEnum, Clone)]
+#[derive(Subcommand, Debug)]
+pub enum GenerationCommand {
+    /// Print the config a generation was created from, with its includes and local overlay merged
+    ShowConfig {
+        /// The ID of the generation, as shown by `list-generations`
+        id: u64,
+        /// The data directory
+        /// Defaults to `$XDG_DATA_HOME/nvim/site/pack/alpacka`
+        #[arg(short, long)]
+        data_dir: Option<PathBuf>,
+        /// Show a generation of a profile instead
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Write the files the config of a generation was loaded from back, keeping the replaced ones as `.bak` files
+    RestoreConfig {
+        /// The ID of the generation, as shown by `list-generations`
+        id: u64,
+        /// The path to the config file. Its includes and local overlay are restored next to it
+        /// Defaults to `$XDG_CONFIG_HOME/nvim/packages.json`, or `packages.lua` if it does not exist
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// The data directory
+        /// Defaults to `$XDG_DATA_HOME/nvim/site/pack/alpacka`
+        #[arg(short, long)]
+        data_dir: Option<PathBuf>,
+        /// Restore a generation of a profile instead
+        #[arg(long)]
+        profile: Option<String>,
+    },
+}
+
+#[derive(Debug, ValueEnum, Clone, Copy)]
 pub enum ListGenerationsFormatMethod {
     /// Human-readable output
     Human,
     /// JSON output, to be parsed by another program
     Json,
 }
+
+#[derive(Debug, ValueEnum, Clone, Copy)]
+pub enum ImportFormatMethod {
+    /// A lazy.nvim `lazy-lock.json` lockfile
+    Lazy,
+    /// A packer.nvim snapshot
+    Packer,
+    /// A vim-plug snapshot, or a file with `Plug` lines
+    VimPlug,
+}
+
+#[derive(Debug, ValueEnum, Clone, Copy)]
+pub enum ExportFormatMethod {
+    /// A lazy.nvim `lazy-lock.json` lockfile
+    Lazy,
+    /// A Neovim `vim.pack` lockfile
+    VimPack,
+    /// A Nix expression using `fetchgit`, with hashes computed from the installed plugins
+    Nix,
+}
@@ -0,0 +1,125 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::Path,
+};
+
+use alpacka::{
+    diff::{diff as diff_manifests, Change, Placement},
+    manifest::{GenerationsFile, Manifest},
+};
+use error_stack::{Context, IntoReport, Report, Result, ResultExt};
+
+use super::clap::ListGenerationsFormatMethod;
+use crate::cli::{peek_generations_file, short};
+
+#[derive(Debug)]
+pub enum Error {
+    Load,
+    UnknownGeneration(u64),
+    Format,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Load => f.write_str("Failed to load generations file"),
+            Self::UnknownGeneration(id) => write!(f, "Generation {id} does not exist"),
+            Self::Format => f.write_str("Failed to format output"),
+        }
+    }
+}
+
+impl Context for Error {}
+
+const fn placement(placement: Placement) -> &'static str {
+    match placement {
+        Placement::Start => "start",
+        Placement::Opt => "opt",
+    }
+}
+
+/// Print a change to a plugin, indented under its name
+fn print_change(change: &Change) {
+    match change {
+        Change::Revision { from, to } => println!("    revision: {} -> {}", short(from), short(to)),
+        Change::Placement { from, to } => {
+            println!("    placement: {} -> {}", placement(*from), placement(*to));
+        }
+        Change::Rename { from, to } => println!(
+            "    rename: {} -> {}",
+            from.as_deref().unwrap_or("none"),
+            to.as_deref().unwrap_or("none")
+        ),
+        Change::Build { from, to } => println!("    build: {from:?} -> {to:?}"),
+        Change::Dependencies { added, removed } => {
+            for dependency in added {
+                println!("    dependency added: {dependency}");
+            }
+
+            for dependency in removed {
+                println!("    dependency removed: {dependency}");
+            }
+        }
+    }
+}
+
+/// Show what changed between two generations of any config, by their IDs
+///
+/// # Errors
+/// Errors if the generations file cannot be loaded, or if a generation does not exist.
+pub fn diff(
+    data_path: &Path,
+    from: u64,
+    to: u64,
+    format_style: &ListGenerationsFormatMethod,
+) -> Result<(), Error> {
+    let generations_path = data_path.join("generations.rkyv");
+    let generations_file =
+        peek_generations_file(&generations_path, None).change_context(Error::Load)?;
+
+    let generations = GenerationsFile::from_bytes(&generations_file)
+        .attach_printable_lazy(|| format!("Generations file path: {}", generations_path.display()))
+        .change_context(Error::Load)?;
+
+    let manifest = |id| -> Result<&Manifest, Error> {
+        generations
+            .get_by_id(id)
+            .map(|(_, generation)| &generation.manifest)
+            .ok_or_else(|| Report::new(Error::UnknownGeneration(id)))
+    };
+
+    let diff = diff_manifests(manifest(from)?, manifest(to)?);
+
+    match format_style {
+        ListGenerationsFormatMethod::Human => {
+            if diff.is_empty() {
+                println!("Generations {from} and {to} install the same plugins");
+            }
+
+            for name in &diff.added {
+                println!("+ {name}");
+            }
+
+            for name in &diff.removed {
+                println!("- {name}");
+            }
+
+            for plugin in &diff.changed {
+                println!("~ {}", plugin.name);
+
+                for change in &plugin.changes {
+                    print_change(change);
+                }
+            }
+        }
+        ListGenerationsFormatMethod::Json => {
+            let json = serde_json::to_string(&diff)
+                .into_report()
+                .change_context(Error::Format)?;
+
+            println!("{json}");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::Path,
+};
+
+use alpacka::{
+    config::ConfigDocument,
+    package::Config as PackageConfig,
+    smith::{enums::Loaders, Git},
+};
+use error_stack::{IntoReport, Report, Result, ResultExt};
+
+#[derive(Debug)]
+pub enum Error {
+    Read,
+    UnsupportedFormat,
+    NoLoaderFound(String),
+    Edit,
+    Write,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read => f.write_str("Failed to read config file"),
+            Self::UnsupportedFormat => f.write_str("Only JSON config files can be edited"),
+            Self::NoLoaderFound(name) => write!(f, "No loader found for package {name}"),
+            Self::Edit => f.write_str("Failed to edit config file"),
+            Self::Write => f.write_str("Failed to write config file"),
+        }
+    }
+}
+
+impl error_stack::Context for Error {}
+
+/// Add a package to a config file
+///
+/// # Errors
+/// Errors if the config cannot be edited, or if no loader can load the package.
+pub fn add(
+    config_path: &Path,
+    name: &str,
+    version: Option<String>,
+    optional: bool,
+) -> Result<(), Error> {
+    let smiths: Vec<Loaders> = vec![Loaders::Git(Git::new())];
+
+    if !smiths
+        .iter()
+        .any(|smith| smith.get_package_name(name).is_some())
+    {
+        return Err(Report::new(Error::NoLoaderFound(name.to_string())));
+    }
+
+    edit_config(config_path, |document| {
+        document.add_package(
+            name,
+            &PackageConfig {
+                optional: optional.then_some(true),
+                ..PackageConfig::default()
+            },
+        )?;
+
+        // the version is set separately, so that it is validated
+        document.set_version(name, version.as_deref())
+    })?;
+
+    tracing::info!("Added {name} to {}", config_path.display());
+
+    Ok(())
+}
+
+/// Remove a package from a config file
+///
+/// # Errors
+/// Errors if the config cannot be edited, or if the package is not in the config.
+pub fn remove(config_path: &Path, name: &str) -> Result<(), Error> {
+    edit_config(config_path, |document| document.remove_package(name))?;
+
+    tracing::info!("Removed {name} from {}", config_path.display());
+
+    Ok(())
+}
+
+/// Read a config file, edit it, and write it back
+fn edit_config(
+    config_path: &Path,
+    edit: impl FnOnce(&mut ConfigDocument) -> Result<(), alpacka::config::EditError>,
+) -> Result<(), Error> {
+    if config_path
+        .extension()
+        .is_some_and(|extension| extension == "lua")
+    {
+        return Err(Report::new(Error::UnsupportedFormat)
+            .attach_printable(format!("Config file path: {}", config_path.display())));
+    }
+
+    let source = std::fs::read_to_string(config_path)
+        .into_report()
+        .attach_printable_lazy(|| format!("Config file path: {}", config_path.display()))
+        .change_context(Error::Read)?;
+
+    let mut document = ConfigDocument::new(source).change_context(Error::Edit)?;
+    edit(&mut document).change_context(Error::Edit)?;
+
+    std::fs::write(config_path, document.as_str())
+        .into_report()
+        .attach_printable_lazy(|| format!("Config file path: {}", config_path.display()))
+        .change_context(Error::Write)
+}
@@ -0,0 +1,89 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::Path,
+};
+
+use alpacka::{
+    condition::ConditionContext,
+    export::ExportFormat,
+    manifest::{get_current, Manifest},
+};
+use error_stack::{Context, IntoReport, Report, Result, ResultExt};
+use rkyv::{Deserialize, Infallible};
+
+use crate::cli::{get_generations_from_file, install::load_config, peek_generations_file};
+
+#[derive(Debug)]
+pub enum Error {
+    Load,
+    NoGeneration,
+    Export,
+    Write,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Load => "Failed to load generation",
+            Self::NoGeneration => {
+                "No generation exists for the current config, run `alpacka install` first"
+            }
+            Self::Export => "Failed to export generation",
+            Self::Write => "Failed to write exported lockfile",
+        })
+    }
+}
+
+impl Context for Error {}
+
+/// Export the generation of the current config to another lockfile format
+///
+/// This is the active generation if it belongs to the config, so a rolled back generation is exported, else the latest one
+///
+/// # Errors
+/// Errors if the config or generations file cannot be loaded, if there is no generation for the config, or if the lockfile cannot be written.
+///
+/// # Panics
+/// Cannot panic, as the only error that can occur when deserializing the manifest is [`Infallible`]
+pub fn export(
+    format: ExportFormat,
+    config_path: &Path,
+    data_path: &Path,
+    profile: Option<&str>,
+    output: Option<&Path>,
+) -> Result<(), Error> {
+    let config = load_config(config_path, profile).change_context(Error::Load)?;
+    let conditions = config.evaluate_conditions(&ConditionContext::detect());
+    let config_hash = config.stable_hash(&conditions);
+
+    let generations_path = data_path.join("generations.rkyv");
+    let generations_file =
+        peek_generations_file(&generations_path, Some((config.legacy_hash(), config_hash)))
+            .change_context(Error::Load)?;
+
+    let generations = get_generations_from_file(&generations_file)
+        .map_err(|_| Error::Load)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("Generations file path: {}", generations_path.display())
+        })?;
+
+    let (_, current) =
+        get_current(generations, config_hash).ok_or_else(|| Report::new(Error::NoGeneration))?;
+    let manifest: Manifest = current.deserialize(&mut Infallible).unwrap();
+
+    let lockfile = format
+        .export(&manifest, data_path)
+        .change_context(Error::Export)?;
+
+    match output {
+        Some(path) => std::fs::write(path, lockfile)
+            .into_report()
+            .attach_printable_lazy(|| format!("Output path: {}", path.display()))
+            .change_context(Error::Write),
+        None => {
+            print!("{lockfile}");
+            Ok(())
+        }
+    }
+}
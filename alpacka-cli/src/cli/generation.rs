@@ -0,0 +1,154 @@
+use std::{
+    ffi::OsString,
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+use alpacka::{
+    config::local_overlay_path,
+    manifest::{ConfigSnapshot, Generation, GenerationsFile},
+};
+use error_stack::{Context, IntoReport, Report, Result, ResultExt};
+
+use crate::cli::peek_generations_file;
+
+#[derive(Debug)]
+pub enum Error {
+    Load,
+    UnknownGeneration(u64),
+    NoConfig(u64),
+    Format(String),
+    Write,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Load => f.write_str("Failed to load generations file"),
+            Self::UnknownGeneration(id) => write!(f, "Generation {id} does not exist"),
+            Self::NoConfig(id) => write!(
+                f,
+                "Generation {id} was created by an older alpacka, which did not record its config"
+            ),
+            Self::Format(path) => write!(
+                f,
+                "The config of the generation was written in {path}, restore it to a config file with the same extension"
+            ),
+            Self::Write => f.write_str("Failed to write config file"),
+        }
+    }
+}
+
+impl Context for Error {}
+
+/// Load the generations file, and get the config snapshot of the generation with an ID
+fn load_snapshot(data_path: &Path, id: u64) -> Result<ConfigSnapshot, Error> {
+    let generations_path = data_path.join("generations.rkyv");
+    let generations_file =
+        peek_generations_file(&generations_path, None).change_context(Error::Load)?;
+
+    let generations = GenerationsFile::from_bytes(&generations_file)
+        .attach_printable_lazy(|| format!("Generations file path: {}", generations_path.display()))
+        .change_context(Error::Load)?;
+
+    let (_, Generation { config, .. }) = generations
+        .get_by_id(id)
+        .ok_or_else(|| Report::new(Error::UnknownGeneration(id)))?;
+
+    config
+        .clone()
+        .ok_or_else(|| Report::new(Error::NoConfig(id)))
+}
+
+/// Print the config a generation was created from, with its includes and local overlay merged
+///
+/// # Errors
+/// Errors if the generations file cannot be loaded, or if the generation does not exist or did not record its config.
+pub fn show_config(data_path: &Path, id: u64) -> Result<(), Error> {
+    let config = load_snapshot(data_path, id)?;
+
+    print!("{}", config.merged);
+
+    Ok(())
+}
+
+/// Keep a file next to itself, with `.bak` appended to its name
+fn backup(path: &Path) -> Result<(), Error> {
+    let mut backup_path = OsString::from(path);
+    backup_path.push(".bak");
+    let backup_path = PathBuf::from(backup_path);
+
+    std::fs::copy(path, &backup_path)
+        .into_report()
+        .attach_printable_lazy(|| format!("Backup path: {}", backup_path.display()))
+        .change_context(Error::Write)
+        .map(|_| ())
+}
+
+/// Write the files the config of a generation was loaded from back to where they were
+///
+/// The config file is written to `config_path`, and its includes and local overlay to their paths relative to it.
+/// Every file which is replaced is kept next to it, with `.bak` appended to its name. A local overlay which the
+/// generation did not have is moved to its `.bak` file, as it would change the restored config.
+///
+/// # Errors
+/// Errors if the generations file cannot be loaded, if the generation does not exist or did not record its config, if
+/// the config of the generation was written in another format than `config_path`, or if a file cannot be written.
+pub fn restore_config(config_path: &Path, data_path: &Path, id: u64) -> Result<(), Error> {
+    let config = load_snapshot(data_path, id)?;
+    let directory = config_path.parent().unwrap_or_else(|| Path::new(""));
+
+    let Some((config_source, includes)) = config.sources.split_first() else {
+        return Err(Report::new(Error::NoConfig(id)));
+    };
+
+    if Path::new(&config_source.path).extension() != config_path.extension() {
+        return Err(Report::new(Error::Format(config_source.path.clone()))
+            .attach_printable(format!("Config file path: {}", config_path.display())));
+    }
+
+    let files = std::iter::once((config_path.to_path_buf(), config_source))
+        .chain(
+            includes
+                .iter()
+                .map(|source| (directory.join(&source.path), source)),
+        )
+        .collect::<Vec<_>>();
+
+    if let Some(overlay_path) = local_overlay_path(config_path)
+        .filter(|overlay_path| overlay_path.exists())
+        .filter(|overlay_path| !files.iter().any(|(path, _)| path == overlay_path))
+    {
+        backup(&overlay_path)?;
+
+        std::fs::remove_file(&overlay_path)
+            .into_report()
+            .attach_printable_lazy(|| format!("Local overlay path: {}", overlay_path.display()))
+            .change_context(Error::Write)?;
+    }
+
+    for (path, source) in files {
+        if path.exists() {
+            backup(&path)?;
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .into_report()
+                .attach_printable_lazy(|| format!("Config file path: {}", path.display()))
+                .change_context(Error::Write)?;
+        }
+
+        std::fs::write(&path, &source.contents)
+            .into_report()
+            .attach_printable_lazy(|| format!("Config file path: {}", path.display()))
+            .change_context(Error::Write)?;
+    }
+
+    println!(
+        "Restored the config of generation {id} to {}, run `alpacka rollback --to {id}` to check out its plugins",
+        config_path.display()
+    );
+
+    Ok(())
+}
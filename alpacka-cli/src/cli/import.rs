@@ -0,0 +1,143 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+use alpacka::{
+    condition::ConditionContext,
+    config::Config,
+    import::{resolve_plugins, to_config, to_plugins, ImportFormat},
+    manifest::{add_to_generations, ConfigSnapshot, GenerationsFile, Manifest, Metadata, Plugin},
+    smith::Git,
+};
+use error_stack::{Context, IntoReport, Report, Result, ResultExt};
+use tracing::info;
+
+use crate::cli::{get_generations_from_file, read_generations_file};
+
+#[derive(Debug)]
+pub enum Error {
+    Read,
+    Import,
+    ConfigExists,
+    Write,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Read => "Failed to read file to import",
+            Self::Import => "Failed to import plugins",
+            Self::ConfigExists => "Config file already exists",
+            Self::Write => "Failed to write imported config",
+        })
+    }
+}
+
+impl Context for Error {}
+
+/// Import the plugins of another package manager into a new config, and create a generation pinned to their commits
+///
+/// # Errors
+/// Errors if the files cannot be imported, if the config already exists, or if the config or generations file cannot be written.
+pub fn import(
+    format: ImportFormat,
+    files: &[PathBuf],
+    plugin_dirs: &[PathBuf],
+    config_path: &Path,
+    data_path: &Path,
+) -> Result<(), Error> {
+    if config_path.exists() {
+        return Err(Report::new(Error::ConfigExists)
+            .attach_printable(format!("Config file path: {}", config_path.display())));
+    }
+
+    let sources = files
+        .iter()
+        .map(|file| {
+            std::fs::read_to_string(file)
+                .into_report()
+                .attach_printable_lazy(|| format!("File path: {}", file.display()))
+                .change_context(Error::Read)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let git = Git::new();
+    let plugins = format.parse(&sources).change_context(Error::Import)?;
+    let packages = resolve_plugins(plugins, plugin_dirs, &git).change_context(Error::Import)?;
+
+    let plugins = to_plugins(&packages, &git).change_context(Error::Import)?;
+
+    std::fs::create_dir_all(data_path)
+        .into_report()
+        .attach_printable_lazy(|| format!("Data directory path: {}", data_path.display()))
+        .change_context(Error::Write)?;
+
+    info!("Resolved {} plugins, writing config", packages.len());
+
+    let json = serde_json::to_string_pretty(&to_config(&packages))
+        .into_report()
+        .change_context(Error::Write)?;
+
+    std::fs::write(config_path, json + "\n")
+        .into_report()
+        .attach_printable_lazy(|| format!("Config file path: {}", config_path.display()))
+        .change_context(Error::Write)?;
+
+    // the config did not exist before, so it is removed again to not leave a config without a generation behind
+    save_generation(config_path, data_path, plugins).inspect_err(|_| {
+        let _ = std::fs::remove_file(config_path);
+    })?;
+
+    info!(
+        "Imported config written to {}, run `alpacka install` to install the plugins",
+        config_path.display()
+    );
+
+    Ok(())
+}
+
+/// Add a generation of the imported plugins to the generations file in the data directory
+fn save_generation(
+    config_path: &Path,
+    data_path: &Path,
+    plugins: Vec<Plugin>,
+) -> Result<(), Error> {
+    // the config is loaded again, so that the generation has the same hash as `alpacka install` computes
+    let (config, sources) = Config::load_with_sources(config_path).change_context(Error::Write)?;
+    let conditions = config.evaluate_conditions(&ConditionContext::detect());
+    let config_hash = config.stable_hash(&conditions);
+    let manifest = Manifest::new("0.9.0".to_string(), plugins, conditions);
+
+    let generations_path = data_path.join("generations.rkyv");
+    let metadata = Metadata::detect(config_path);
+    let snapshot = Some(ConfigSnapshot {
+        merged: config.snapshot(),
+        sources,
+    });
+
+    let generations = if generations_path.exists() {
+        let generations_file =
+            read_generations_file(&generations_path, Some((config.legacy_hash(), config_hash)))
+                .change_context(Error::Read)?;
+
+        let generations = get_generations_from_file(&generations_file)
+            .map_err(|_| Error::Read)
+            .into_report()
+            .attach_printable_lazy(|| {
+                format!("Generations file path: {}", generations_path.display())
+            })?;
+
+        add_to_generations(generations, config_hash, manifest, metadata, snapshot)
+    } else {
+        let mut generations = GenerationsFile::new();
+        generations.add_to_generations(config_hash, manifest, metadata, snapshot);
+        generations
+    };
+
+    generations
+        .save_to_file(&generations_path)
+        .into_report()
+        .attach_printable_lazy(|| format!("Generations file path: {}", generations_path.display()))
+        .change_context(Error::Write)
+}
@@ -1,27 +1,34 @@
 use alpacka::{
+    condition::{ConditionContext, ConditionOutcome},
     config::Config,
+    graph::DependencyGraph,
     manifest::{
-        add_to_generations, get_latest, ArchivedGenerationsFile, GenerationsFile, Manifest, Plugin,
+        add_to_generations, get_current, get_installed, get_most_recent, ArchivedGenerationsFile,
+        ConfigHash, ConfigSnapshot, GenerationHash, GenerationsFile, Manifest, Metadata, Plugin,
+        Resolution,
     },
     package::{Config as PackageConfig, Package, WithSmith},
+    plan::{plan, stale_plugins, Action},
     smith::{enums::Loaders, Git},
 };
 use error_stack::{Context, IntoReport, Result, ResultExt};
 use rayon::prelude::*;
-use rkyv::{to_bytes, Deserialize, Infallible};
+use rkyv::{Deserialize, Infallible};
 use std::{
-    collections::{hash_map::DefaultHasher, BTreeMap},
     fmt::{Display, Formatter},
     fs::File,
-    hash::{Hash, Hasher},
     io::{BufRead, BufReader, Write},
     path::{Path, PathBuf},
     process::{ChildStderr, ChildStdout, Command, Stdio},
     thread::{self, Scope},
+    time::Instant,
 };
 use tracing::{debug, info, warn};
 
-use crate::cli::get_generations_from_file;
+use crate::cli::{
+    get_generations_from_file, peek_generations_file, read_generations_file, set_active_generation,
+    short,
+};
 
 #[derive(Debug)]
 pub enum Error {
@@ -40,13 +47,22 @@ impl Display for Error {
 
 impl Context for Error {}
 
-/// Installs the latest generation of plugins
+/// Installs the latest generation of plugins, optionally only installing the packages of a profile
+///
+/// The description is recorded in the metadata of the generation, if a new generation is created. With `dry_run`,
+/// the plan of what installing would change is printed instead, and nothing is written
 ///
 /// # Errors
 /// Errors if the config file cannot be opened, or if the generations file cannot be fetched.
 /// May also error if a install command cannot be run.
-pub fn install(config_path: PathBuf, data_path: &PathBuf) -> Result<(), Error> {
-    if !data_path.exists() {
+pub fn install(
+    config_path: PathBuf,
+    data_path: &PathBuf,
+    profile: Option<&str>,
+    description: Option<&str>,
+    dry_run: bool,
+) -> Result<(), Error> {
+    if !data_path.exists() && !dry_run {
         std::fs::create_dir_all(data_path)
             .into_report()
             .attach_printable_lazy(|| {
@@ -58,102 +74,340 @@ pub fn install(config_path: PathBuf, data_path: &PathBuf) -> Result<(), Error> {
             .change_context(Error::Load)?;
     }
 
-    load_alpacka(data_path, config_path)?;
+    load_alpacka(data_path, &config_path, profile, description, dry_run)?;
 
     Ok(())
 }
 
-fn load_alpacka(data_path: &Path, config_path: PathBuf) -> Result<(), Error> {
-    let config_file = std::fs::File::open(config_path)
-        .into_report()
-        .attach_printable_lazy(|| "Failed to open config file".to_string())
-        .change_context(Error::Load)?;
+/// Load a config, and only keep the packages of a profile if one is given
+pub(crate) fn load_config(config_path: &Path, profile: Option<&str>) -> Result<Config, Error> {
+    load_config_with_snapshot(config_path, profile).map(|(config, _)| config)
+}
 
-    let config: Config = serde_json::from_reader(config_file)
-        .into_report()
-        .attach_printable_lazy(|| "Failed to parse config file".to_string())
-        .change_context(Error::Load)?;
+/// Load a config like [`load_config`], and also return its snapshot from before the profile was selected
+pub(crate) fn load_config_with_snapshot(
+    config_path: &Path,
+    profile: Option<&str>,
+) -> Result<(Config, ConfigSnapshot), Error> {
+    let (mut config, sources) =
+        Config::load_with_sources(config_path).change_context(Error::Load)?;
+    let snapshot = ConfigSnapshot {
+        merged: config.snapshot(),
+        sources,
+    };
 
-    info!("Config loaded, checking for existing manifest");
+    if let Some(profile) = profile {
+        config.select_profile(profile).change_context(Error::Load)?;
+    }
 
-    let config_hash = {
-        let mut hasher = DefaultHasher::new();
-        config.hash(&mut hasher);
-        hasher.finish()
-    };
+    Ok((config, snapshot))
+}
+
+fn load_alpacka(
+    data_path: &Path,
+    config_path: &Path,
+    profile: Option<&str>,
+    description: Option<&str>,
+    dry_run: bool,
+) -> Result<(), Error> {
+    let (config, snapshot) = load_config_with_snapshot(config_path, profile)?;
+
+    info!("Config loaded, evaluating package conditions");
+
+    let context = ConditionContext::detect();
+    let conditions = config.evaluate_conditions(&context);
+
+    debug!("Condition outcomes: {:#?}", conditions);
+    info!("Conditions evaluated, checking for existing manifest");
+
+    let config_hash = config.stable_hash(&conditions);
 
     let smiths: Vec<Loaders> = vec![Loaders::Git(Git::new())];
     let generation_path = data_path.join("generations.rkyv");
+    let metadata = Metadata {
+        description: description.map(ToString::to_string),
+        ..Metadata::detect(config_path)
+    };
+
+    let generations_file = if generation_path.exists() {
+        let read = if dry_run {
+            peek_generations_file
+        } else {
+            read_generations_file
+        };
 
-    let manifest = if generation_path.exists() {
-        let generations_file = std::fs::read(&generation_path)
+        Some(
+            read(&generation_path, Some((config.legacy_hash(), config_hash)))
+                .attach_printable_lazy(|| {
+                    format!(
+                        "Failed to read generations file. Generations file path: {}",
+                        generation_path.display()
+                    )
+                })
+                .change_context(Error::Load)?,
+        )
+    } else {
+        None
+    };
+
+    let generations = generations_file
+        .as_deref()
+        .map(|generations_file| {
+            get_generations_from_file(generations_file)
+                .map_err(|_| Error::Load)
+                .into_report()
+                .attach_printable_lazy(|| {
+                    format!(
+                        "Failed to parse generations file. Generations file path: {}",
+                        generation_path.display()
+                    )
+                })
+        })
+        .transpose()?;
+
+    let installed: Option<Manifest> = generations
+        .and_then(get_installed)
+        .map(|manifest| manifest.deserialize(&mut Infallible).unwrap());
+
+    // prefer the active generation if it belongs to the current config, else its highest generation
+    let current = generations.and_then(|generations| get_current(generations, config_hash));
+
+    if dry_run {
+        let manifest = match current {
+            Some((_, manifest)) => manifest.deserialize(&mut Infallible).unwrap(),
+            None => resolve_manifest(&smiths, &config, &context, conditions, generations)?,
+        };
+
+        print_plan(&plan(installed.as_ref(), &manifest, data_path), data_path);
+
+        return Ok(());
+    }
+
+    let (hash, manifest) = match current {
+        Some((generation, manifest)) => {
+            info!("Found generation with the same hash as the current config, loading manifest");
+            if description.is_some() {
+                warn!("No new generation was created, so the description is not recorded");
+            }
+
+            let manifest: Manifest = manifest.deserialize(&mut Infallible).unwrap();
+            (GenerationHash(config_hash, generation), manifest)
+        }
+        None => create_manifest_from_config(
+            &smiths,
+            &config,
+            &context,
+            conditions,
+            config_hash,
+            &generation_path,
+            generations,
+            metadata,
+            snapshot,
+        )?,
+    };
+
+    info!("Manifest loaded, creating packages");
+
+    load_manifest(&smiths, &manifest, data_path)?;
+    remove_stale(installed.as_ref(), &manifest, data_path)?;
+
+    set_active_generation(&generation_path, hash)
+        .attach_printable_lazy(|| format!("Generation: {}", hash.1))
+        .change_context(Error::Load)
+}
+
+/// Remove the plugins of the installed manifest which a newly installed manifest does not use
+pub(crate) fn remove_stale(
+    installed: Option<&Manifest>,
+    manifest: &Manifest,
+    data_path: &Path,
+) -> Result<(), Error> {
+    let stale = installed
+        .map(|installed| stale_plugins(installed, manifest, data_path))
+        .unwrap_or_default();
+
+    for (plugin, path) in stale {
+        info!("Removing {}", plugin.unresolved_name);
+
+        std::fs::remove_dir_all(&path)
             .into_report()
             .attach_printable_lazy(|| {
                 format!(
-                    "Failed to read generations file. Generations file path: {}",
-                    generation_path.display()
+                    "Failed to remove plugin. Package name: {}, Package path: {}",
+                    plugin.unresolved_name,
+                    path.display()
                 )
             })
-            .change_context(Error::Load)?;
+            .change_context(Error::LoadManifest)?;
+    }
 
-        let generations = get_generations_from_file(&generations_file)
-            .map_err(|_| Error::Load)
-            .into_report()
-            .attach_printable_lazy(|| {
-                format!(
-                    "Failed to parse generations file. Generations file path: {}",
-                    generation_path.display()
-                )
-            })?;
-
-        // find generation that have the same hash as the current config, and the highest generation
-        get_latest(generations, config_hash).map_or_else(
-            || create_manifest_from_config(&smiths, &config, &generation_path, Some(generations)),
-            |manifest| {
-                info!(
-                    "Found generation with the same hash as the current config, loading manifest"
-                );
-                let manifest: Manifest = manifest.deserialize(&mut Infallible).unwrap();
-                Ok(manifest)
-            },
-        )
-    } else {
-        create_manifest_from_config(&smiths, &config, &generation_path, None)
-    }?;
+    Ok(())
+}
 
-    info!("Manifest loaded, creating packages");
+/// Print what installing a manifest would change in the data directory
+pub(crate) fn print_plan(actions: &[Action], data_path: &Path) {
+    let relative = |path: &Path| {
+        path.strip_prefix(data_path)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+
+    if actions.is_empty() {
+        println!("Nothing to do");
+    }
+
+    for action in actions {
+        match action {
+            Action::Clone {
+                name,
+                revision,
+                path,
+            } => println!(
+                "clone {name} at {} into {}",
+                short(revision),
+                relative(path)
+            ),
+            Action::Update {
+                name,
+                from: Some(from),
+                to,
+            } => println!("update {name} {} -> {}", short(from), short(to)),
+            Action::Update {
+                name,
+                from: None,
+                to,
+            } => println!("check out {name} at {}", short(to)),
+            Action::Remove { name, path } => println!("remove {name} from {}", relative(path)),
+            Action::Build { name, command } => println!("build {name}: {command}"),
+        }
+    }
+}
 
+/// Load every plugin of a manifest into the data directory
+pub(crate) fn load_manifest(
+    smiths: &[Loaders],
+    manifest: &Manifest,
+    data_path: &Path,
+) -> Result<(), Error> {
     manifest
         .plugins
         .par_iter()
-        .map(|plugin| load_plugin(&smiths, plugin, data_path))
-        .collect::<Result<_, _>>()?;
-
-    Ok(())
+        .map(|plugin| load_plugin(smiths, plugin, data_path))
+        .collect::<Result<(), _>>()
 }
 
 #[tracing::instrument(skip(generations))]
+#[allow(clippy::too_many_arguments)]
 fn create_manifest_from_config(
     smiths: &[Loaders],
     config: &Config,
+    context: &ConditionContext,
+    conditions: Vec<ConditionOutcome>,
+    config_hash: ConfigHash,
     generations_path: &Path,
     generations: Option<&ArchivedGenerationsFile>,
+    mut metadata: Metadata,
+    snapshot: ConfigSnapshot,
+) -> Result<(GenerationHash, Manifest), Error> {
+    let started = Instant::now();
+    let manifest = resolve_manifest(smiths, config, context, conditions, generations)?;
+
+    metadata.resolution_duration_ms = u64::try_from(started.elapsed().as_millis()).ok();
+
+    info!("resolved manifest, saving");
+
+    let new_generations_file = if let Some(generations) = generations {
+        add_to_generations(generations, config_hash, manifest, metadata, Some(snapshot))
+    } else {
+        let mut gen_file = GenerationsFile::new();
+        gen_file.add_to_generations(config_hash, manifest, metadata, Some(snapshot));
+        gen_file
+    };
+
+    // overwrite the generations file
+    let mut file = File::create(generations_path)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!(
+                "Failed to create generations file. Path: {}",
+                generations_path.display()
+            )
+        })
+        .change_context(Error::Load)?;
+
+    let bytes = new_generations_file
+        .to_bytes()
+        .attach_printable_lazy(|| "Failed to serialize generations file")
+        .change_context(Error::Load)?;
+
+    file.write_all(&bytes)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!(
+                "Failed to write to generations file. Path: {}",
+                generations_path.display()
+            )
+        })
+        .change_context(Error::Load)?;
+
+    info!("generations file saved, getting latest manifest");
+
+    new_generations_file
+        .generations
+        .into_iter()
+        .rev()
+        .find(|(hash, _)| hash.0 == config_hash)
+        .map(|(hash, generation)| (hash, generation.manifest))
+        .ok_or(Error::Load)
+        .into_report()
+        .attach_printable_lazy(|| "Failed to get latest manifest")
+        .change_context(Error::LoadManifest)
+}
+
+/// Resolve the packages of a config into a manifest, reusing the inputs of unchanged packages from the most recent
+/// generation
+fn resolve_manifest(
+    smiths: &[Loaders],
+    config: &Config,
+    context: &ConditionContext,
+    conditions: Vec<ConditionOutcome>,
+    generations: Option<&ArchivedGenerationsFile>,
 ) -> Result<Manifest, Error> {
-    let packages = config
-        .create_package_list(smiths)
-        .attach_printable_lazy(|| "Failed to create package list")
+    let graph = DependencyGraph::new(config, smiths, context)
+        .attach_printable_lazy(|| "Failed to build dependency graph")
         .change_context(Error::LoadManifest)?;
 
-    info!("packages created, resolving");
+    let previous: Option<Manifest> = generations
+        .and_then(get_most_recent)
+        .map(|manifest| manifest.deserialize(&mut Infallible).unwrap());
+
+    let packages = graph.packages();
+    let reusable_inputs = |package: &WithSmith| {
+        previous
+            .as_ref()
+            .and_then(|previous| previous.reusable_inputs(package))
+    };
+    let reused = packages
+        .iter()
+        .filter(|package| reusable_inputs(package).is_some())
+        .count();
+
+    info!(
+        "dependency graph built, reusing {reused} unchanged packages and resolving {}",
+        packages.len() - reused
+    );
+
     let resolved_packages = packages
         .into_par_iter()
-        .map(|package| package.resolve_recurse(smiths))
+        .map(|package| {
+            reusable_inputs(&package)
+                .map_or_else(|| package.resolve(smiths), |inputs| Ok(inputs.clone()))
+                .map(|loader_data| (loader_data, package))
+        })
         .collect::<Result<Vec<_>, _>>()
         .change_context(Error::LoadManifest)
-        .attach_printable_lazy(|| "Failed to resolve packages!")?
-        .into_iter()
-        .flatten()
-        .collect::<Vec<_>>();
+        .attach_printable_lazy(|| "Failed to resolve packages!")?;
 
     debug!("Resolved packages: {:#?}", resolved_packages);
 
@@ -174,8 +428,10 @@ fn create_manifest_from_config(
                 package,
             } = package;
 
+            let resolved_from = Resolution::of(&package);
             let Package {
                 name,
+                source: _,
                 config_package,
             } = package;
 
@@ -185,6 +441,9 @@ fn create_manifest_from_config(
                 dependencies,
                 rename,
                 version: _,
+                when: _,
+                disabled: _,
+                patches: _,
             } = config_package;
 
             let plugin = Plugin {
@@ -202,96 +461,34 @@ fn create_manifest_from_config(
                 build: build.clone().unwrap_or_default(),
                 smith: smith_to_use,
                 loader_data,
+                overridden: config.overrides.get(name).cloned(),
+                patches: config.patches(config_package),
+                resolved_from: Some(resolved_from),
             };
 
             Ok(plugin)
         })
         .collect::<Result<Vec<_>, _>>()?;
 
-    let manifest = Manifest {
+    Ok(Manifest {
         neovim_version: "0.9.0".to_string(),
         plugins,
-    };
-
-    info!("resolved manifest, saving");
-
-    let hash = {
-        let mut hasher = DefaultHasher::new();
-        config.hash(&mut hasher);
-        hasher.finish()
-    };
-
-    let new_generations_file = if let Some(generations) = generations {
-        add_to_generations(generations, hash, manifest)
-    } else {
-        let mut gen_file = GenerationsFile(BTreeMap::new());
-        gen_file.add_to_generations(hash, manifest);
-        gen_file
-    };
-
-    // overwrite the generations file
-    let mut file = File::create(generations_path)
-        .into_report()
-        .attach_printable_lazy(|| {
-            format!(
-                "Failed to create generations file. Path: {}",
-                generations_path.display()
-            )
-        })
-        .change_context(Error::Load)?;
-
-    let bytes = to_bytes::<_, 1024>(&new_generations_file)
-        .into_report()
-        .attach_printable_lazy(|| "Failed to serialize generations file")
-        .change_context(Error::Load)?;
-
-    file.write_all(&bytes)
-        .into_report()
-        .attach_printable_lazy(|| {
-            format!(
-                "Failed to write to generations file. Path: {}",
-                generations_path.display()
-            )
-        })
-        .change_context(Error::Load)?;
-
-    info!("generations file saved, getting latest manifest");
-
-    let manifest = new_generations_file
-        .0
-        .into_iter()
-        .last()
-        .ok_or(Error::Load)
-        .into_report()
-        .attach_printable_lazy(|| "Failed to get latest manifest")
-        .change_context(Error::LoadManifest)?;
-
-    Ok(manifest.1)
+        conditions,
+    })
 }
 
 #[tracing::instrument]
 fn load_plugin(smiths: &[Loaders], plugin: &Plugin, data_path: &Path) -> Result<(), Error> {
-    let smith = smiths
-        .iter()
-        .find(|s| s.name() == plugin.smith)
-        .ok_or(Error::LoadManifest)
-        .into_report()
-        .attach_printable_lazy(|| format!("Failed to find smith. Smith name: {}", plugin.smith))?;
+    plugin
+        .checkout(smiths, data_path)
+        .change_context(Error::LoadManifest)?;
 
-    let package_path = data_path
-        .join(if plugin.optional { "opt" } else { "start" })
-        .join(plugin.rename.as_ref().unwrap_or(&plugin.name));
+    run_build(plugin, data_path)
+}
 
-    smith
-        .load(&plugin.loader_data, &package_path)
-        .attach_printable_lazy(|| {
-            format!(
-                "Failed to load package. Package name: {}, Package path: {}",
-                plugin.name,
-                package_path.display()
-            )
-        })
-        .change_context(Error::LoadManifest)?;
+/// Run the build command of a plugin in its directory, if it has one
+pub(crate) fn run_build(plugin: &Plugin, data_path: &Path) -> Result<(), Error> {
+    let package_path = plugin.install_path(data_path);
 
     let build_script_exists = !plugin.build.is_empty();
     if build_script_exists {
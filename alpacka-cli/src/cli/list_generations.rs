@@ -1,7 +1,5 @@
 use std::{
-    collections::BTreeMap,
     fmt::{Display, Formatter},
-    iter::once,
     path::Path,
 };
 
@@ -9,9 +7,12 @@ use error_stack::{ensure, Context, IntoReport, Result, ResultExt};
 use rkyv::{Deserialize, Infallible};
 use tracing::{error, info};
 
-use alpacka::manifest::{GenerationsFile, Json, JsonGenerationsFile, Manifest};
+use alpacka::{
+    changelog::date_time,
+    manifest::{GenerationsFile, Json, JsonGenerationsFile},
+};
 
-use crate::cli::get_generations_from_file;
+use crate::cli::{get_generations_from_file, peek_generations_file};
 
 use super::clap::ListGenerationsFormatMethod;
 
@@ -50,8 +51,7 @@ pub fn list_generations(
         Error::LoadError
     });
 
-    let generations_file = std::fs::read(&generations_path)
-        .into_report()
+    let generations_file = peek_generations_file(&generations_path, None)
         .attach_printable_lazy(|| {
             format!(
                 "Failed to read generations file. Generations file path: {}",
@@ -70,41 +70,82 @@ pub fn list_generations(
         })
         .into_report()?;
 
+    let generations: GenerationsFile = generations.deserialize(&mut Infallible).unwrap();
+
     match format_style {
         ListGenerationsFormatMethod::Human => {
-            for (idx, (hash, manifest)) in generations.0.iter().enumerate() {
-                let hashed_config_file = hash.0;
-                let generation_number = hash.1;
-
-                // TODO: use the manifest for output
-                let _manifest: Manifest = manifest.deserialize(&mut Infallible).unwrap();
-
-                info!("Manifest number {idx} | Hash {hashed_config_file} | generation {generation_number}");
+            for (hash, generation) in generations.timeline() {
+                let created_at = if generation.created_at == 0 {
+                    "unknown".to_string()
+                } else {
+                    date_time(generation.created_at)
+                };
+
+                info!(
+                    "Generation {} | Created {created_at} | Hash {}{}",
+                    generation.id,
+                    hash.0,
+                    if generations.active == Some(*hash) {
+                        " | active"
+                    } else {
+                        ""
+                    }
+                );
+
+                let metadata = &generation.metadata;
+                let details: Vec<String> = [
+                    metadata
+                        .description
+                        .as_ref()
+                        .map(|description| format!("\"{description}\"")),
+                    metadata
+                        .alpacka_version
+                        .as_ref()
+                        .map(|version| format!("alpacka {version}")),
+                    metadata
+                        .hostname
+                        .as_ref()
+                        .map(|hostname| format!("on {hostname}")),
+                    metadata
+                        .config_path
+                        .as_ref()
+                        .map(|config_path| format!("from {config_path}")),
+                    metadata
+                        .resolution_duration_ms
+                        .map(|duration| format!("resolved in {duration}ms")),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+
+                if !details.is_empty() {
+                    info!("  {}", details.join(" | "));
+                }
             }
         }
         ListGenerationsFormatMethod::Json => {
-            let deserialized: GenerationsFile = generations.deserialize(&mut Infallible).unwrap();
-
-            // this is possibly the most cursed solution to this
-            let json = deserialized.0.into_iter().fold(
-                JsonGenerationsFile(BTreeMap::new()),
-                |current, (hash, manifest)| {
-                    let new_map = current
-                        .0
-                        .into_iter()
-                        .chain(once((
-                            hash.0.to_string(),
+            // generations are keyed by their ID, as a config hash can have several generations
+            let json = JsonGenerationsFile(
+                generations
+                    .generations
+                    .into_iter()
+                    .map(|(hash, generation)| {
+                        (
+                            generation.id.to_string(),
                             Json {
                                 hash: hash.0.to_string(),
                                 generation: hash.1.to_string(),
-                                neovim_version: manifest.neovim_version,
-                                plugins: manifest.plugins,
+                                id: generation.id.to_string(),
+                                created_at: generation.created_at.to_string(),
+                                active: generations.active == Some(hash),
+                                metadata: generation.metadata,
+                                neovim_version: generation.manifest.neovim_version,
+                                plugins: generation.manifest.plugins,
+                                conditions: generation.manifest.conditions,
                             },
-                        )))
-                        .collect();
-
-                    JsonGenerationsFile(new_map)
-                },
+                        )
+                    })
+                    .collect(),
             );
 
             let json = serde_json::to_string(&json)
@@ -1,18 +1,123 @@
-use bytecheck::TupleStructCheckError;
-use rkyv::{
-    check_archived_root,
-    validation::{validators::DefaultValidatorError, CheckArchiveError},
-};
+use std::path::Path;
 
-use alpacka::manifest::{ArchivedGenerationsFile, GenerationsFile};
+use alpacka::manifest::{
+    archived_generations, format_version, ArchivedGenerationsFile, ConfigHash, GenerationHash,
+    GenerationsFile, GenerationsFileError, FORMAT_VERSION,
+};
+use error_stack::{IntoReport, Result, ResultExt};
+use tracing::info;
 
+pub mod changelog;
 pub mod clap;
+pub mod diff;
+pub mod edit;
+pub mod export;
+pub mod generation;
+pub mod import;
 pub mod install;
 pub mod list_generations;
+pub mod outdated;
+pub mod rollback;
+pub mod schema;
+pub mod update;
+
+/// Shorten a commit hash for display
+pub(crate) fn short(revision: &str) -> &str {
+    revision.get(..7).unwrap_or(revision)
+}
 
 pub(crate) fn get_generations_from_file(
     generations_file: &[u8],
-) -> Result<&ArchivedGenerationsFile, CheckArchiveError<TupleStructCheckError, DefaultValidatorError>>
-{
-    check_archived_root::<GenerationsFile>(generations_file)
+) -> Result<&ArchivedGenerationsFile, GenerationsFileError> {
+    archived_generations(generations_file)
+}
+
+/// Migrate the bytes of a generations file written by an older alpacka
+///
+/// `current` is the legacy and stable hash of the current config. Its generations are moved to the stable hash,
+/// while the others keep their legacy hash. Returns [`None`] if the file is already in the current format, or in a
+/// newer one which this alpacka cannot read.
+fn migrate_generations(
+    bytes: &[u8],
+    current: Option<(u64, ConfigHash)>,
+) -> Result<Option<Vec<u8>>, GenerationsFileError> {
+    if format_version(bytes).is_some_and(|version| version >= FORMAT_VERSION) {
+        return Ok(None);
+    }
+
+    info!("Migrating generations file written by an older alpacka");
+
+    let mut generations = GenerationsFile::from_bytes(bytes)?;
+
+    if let Some((legacy_hash, config_hash)) = current {
+        if generations.adopt_legacy(legacy_hash, config_hash) {
+            info!("Moved the generations of the current config to its new hash");
+        }
+    }
+
+    generations.to_bytes().map(Some)
+}
+
+/// Read a generations file, migrating it first if it was written by an older alpacka
+///
+/// See [`migrate_generations`] for how generations are migrated. The old file is kept as `generations.rkyv.legacy`.
+/// Only commands which change the generations file and pass the hashes of the current config should persist a
+/// migration, since its generations would otherwise keep their legacy hash for good. Every other command uses
+/// [`peek_generations_file`] instead.
+pub(crate) fn read_generations_file(
+    path: &Path,
+    current: Option<(u64, ConfigHash)>,
+) -> Result<Vec<u8>, GenerationsFileError> {
+    let bytes = std::fs::read(path)
+        .into_report()
+        .attach_printable_lazy(|| format!("Generations file path: {}", path.display()))
+        .change_context(GenerationsFileError::Read)?;
+
+    let Some(migrated) = migrate_generations(&bytes, current)? else {
+        return Ok(bytes);
+    };
+
+    std::fs::write(path.with_extension("rkyv.legacy"), &bytes)
+        .and_then(|()| std::fs::write(path, &migrated))
+        .into_report()
+        .attach_printable_lazy(|| format!("Generations file path: {}", path.display()))
+        .change_context(GenerationsFileError::Write)?;
+
+    Ok(migrated)
+}
+
+/// Read a generations file like [`read_generations_file`], but only migrate it in memory, so nothing is written
+pub(crate) fn peek_generations_file(
+    path: &Path,
+    current: Option<(u64, ConfigHash)>,
+) -> Result<Vec<u8>, GenerationsFileError> {
+    let bytes = std::fs::read(path)
+        .into_report()
+        .attach_printable_lazy(|| format!("Generations file path: {}", path.display()))
+        .change_context(GenerationsFileError::Read)?;
+
+    Ok(migrate_generations(&bytes, current)?.unwrap_or(bytes))
+}
+
+/// Mark a generation as the active one in the generations file, if it isn't already
+pub(crate) fn set_active_generation(
+    path: &Path,
+    hash: GenerationHash,
+) -> Result<(), GenerationsFileError> {
+    let bytes = std::fs::read(path)
+        .into_report()
+        .attach_printable_lazy(|| format!("Generations file path: {}", path.display()))
+        .change_context(GenerationsFileError::Read)?;
+
+    let mut generations = GenerationsFile::from_bytes(&bytes)?;
+    if generations.active == Some(hash) {
+        return Ok(());
+    }
+
+    generations.active = Some(hash);
+
+    std::fs::write(path, generations.to_bytes()?)
+        .into_report()
+        .attach_printable_lazy(|| format!("Generations file path: {}", path.display()))
+        .change_context(GenerationsFileError::Write)
 }
@@ -0,0 +1,141 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::Path,
+};
+
+use alpacka::{
+    condition::ConditionContext,
+    manifest::{get_current, Manifest},
+    outdated::{check, Outdated, Status},
+    smith::{enums::Loaders, Git},
+};
+use error_stack::{Context, IntoReport, Report, Result, ResultExt};
+use rkyv::{Deserialize, Infallible};
+
+use crate::cli::{get_generations_from_file, install::load_config, peek_generations_file, short};
+
+use super::clap::ListGenerationsFormatMethod;
+
+#[derive(Debug)]
+pub enum Error {
+    Load,
+    NoGeneration,
+    Check,
+    Format,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Load => "Failed to load generation",
+            Self::NoGeneration => {
+                "No generation exists for the current config, run `alpacka install` first"
+            }
+            Self::Check => "Failed to check packages for updates",
+            Self::Format => "Failed to format output",
+        })
+    }
+}
+
+impl Context for Error {}
+
+/// Describe how an outdated plugin compares to its remote
+fn describe(outdated: &Outdated) -> String {
+    match &outdated.status {
+        Status::Behind {
+            commits: Some(commits),
+            latest,
+            ..
+        } => format!("{commits} new commits, latest {}", short(latest)),
+        Status::Behind {
+            commits: None,
+            latest,
+            ..
+        } => format!(
+            "the branch no longer contains {}, latest {}",
+            short(&outdated.revision),
+            short(latest)
+        ),
+        Status::NewerTags { tags, .. } => format!("newer tags {}", tags.join(", ")),
+        Status::UpToDate | Status::Pinned => "up to date".to_string(),
+    }
+}
+
+/// Check the plugins of the current config's generation against their remotes, without changing anything
+///
+/// This is the active generation if it belongs to the config, so a rolled back generation is checked, else the latest one
+///
+/// # Errors
+/// Errors if the config or generations file cannot be loaded, if there is no generation for the config, or if a
+/// remote cannot be reached.
+///
+/// # Panics
+/// Cannot panic, as the only error that can occur when deserializing the manifest is [`Infallible`]
+pub fn outdated(
+    config_path: &Path,
+    data_path: &Path,
+    profile: Option<&str>,
+    format_style: &ListGenerationsFormatMethod,
+) -> Result<(), Error> {
+    let config = load_config(config_path, profile).change_context(Error::Load)?;
+    let conditions = config.evaluate_conditions(&ConditionContext::detect());
+    let config_hash = config.stable_hash(&conditions);
+
+    let generations_path = data_path.join("generations.rkyv");
+    let generations_file =
+        peek_generations_file(&generations_path, Some((config.legacy_hash(), config_hash)))
+            .change_context(Error::Load)?;
+
+    let generations = get_generations_from_file(&generations_file)
+        .map_err(|_| Error::Load)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("Generations file path: {}", generations_path.display())
+        })?;
+
+    let (_, current) =
+        get_current(generations, config_hash).ok_or_else(|| Report::new(Error::NoGeneration))?;
+    let manifest: Manifest = current.deserialize(&mut Infallible).unwrap();
+
+    let smiths: Vec<Loaders> = vec![Loaders::Git(Git::new())];
+    let report = check(&manifest, &smiths).change_context(Error::Check)?;
+
+    match format_style {
+        ListGenerationsFormatMethod::Human => {
+            let outdated = report
+                .iter()
+                .filter(|outdated| outdated.status.is_outdated())
+                .collect::<Vec<_>>();
+
+            if outdated.is_empty() {
+                println!("Everything is up to date");
+            }
+
+            for outdated in outdated {
+                println!(
+                    "{} ({}): {}",
+                    outdated.name,
+                    outdated.version.as_deref().unwrap_or("default branch"),
+                    describe(outdated)
+                );
+
+                if let Status::Behind { breaking, .. } | Status::NewerTags { breaking, .. } =
+                    &outdated.status
+                {
+                    for change in breaking {
+                        println!("  breaking: {change}");
+                    }
+                }
+            }
+        }
+        ListGenerationsFormatMethod::Json => {
+            let json = serde_json::to_string(&report)
+                .into_report()
+                .change_context(Error::Format)?;
+
+            println!("{json}");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::Path,
+};
+
+use alpacka::{
+    condition::ConditionContext,
+    manifest::GenerationsFile,
+    rollback::rollback as rollback_generations,
+    smith::{enums::Loaders, Git},
+};
+use error_stack::{Context, IntoReport, Result, ResultExt};
+use rayon::prelude::*;
+use tracing::warn;
+
+use crate::cli::{
+    install::{load_config, run_build},
+    read_generations_file,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    Load,
+    Rollback,
+    Build,
+    Write,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Load => "Failed to load generation",
+            Self::Rollback => "Failed to roll back",
+            Self::Build => "Failed to build packages",
+            Self::Write => "Failed to write generations file",
+        })
+    }
+}
+
+impl Context for Error {}
+
+/// Roll back the current config to a previous generation, or to the generation with the ID `to`
+///
+/// The generation with the ID `to` can belong to another config, in which case a warning says to restore that config,
+/// as `alpacka install` replaces the generation otherwise
+///
+/// # Errors
+/// Errors if the config or generations file cannot be loaded, if there is no generation to roll back to, if the
+/// plugins cannot be checked out or built, or if the generations file cannot be written.
+pub fn rollback(
+    config_path: &Path,
+    data_path: &Path,
+    profile: Option<&str>,
+    to: Option<u64>,
+) -> Result<(), Error> {
+    let config = load_config(config_path, profile).change_context(Error::Load)?;
+    let conditions = config.evaluate_conditions(&ConditionContext::detect());
+    let config_hash = config.stable_hash(&conditions);
+
+    let generations_path = data_path.join("generations.rkyv");
+    let generations_file =
+        read_generations_file(&generations_path, Some((config.legacy_hash(), config_hash)))
+            .change_context(Error::Load)?;
+
+    let mut generations = GenerationsFile::from_bytes(&generations_file)
+        .attach_printable_lazy(|| format!("Generations file path: {}", generations_path.display()))
+        .change_context(Error::Load)?;
+
+    let smiths: Vec<Loaders> = vec![Loaders::Git(Git::new())];
+    let target = rollback_generations(&mut generations, config_hash, to, &smiths, data_path)
+        .change_context(Error::Rollback)?;
+
+    let generation = &generations.generations[&target];
+
+    generation
+        .manifest
+        .plugins
+        .par_iter()
+        .map(|plugin| run_build(plugin, data_path).change_context(Error::Build))
+        .collect::<Result<(), _>>()?;
+
+    generations
+        .save_to_file(&generations_path)
+        .into_report()
+        .attach_printable_lazy(|| format!("Generations file path: {}", generations_path.display()))
+        .change_context(Error::Write)?;
+
+    println!("Rolled back to generation {}", generation.id);
+
+    if target.0 != config_hash {
+        warn!(
+            "Generation {id} was created from another config, and is replaced by the next `alpacka install` unless \
+             that config is restored with `alpacka generation restore-config {id}`",
+            id = generation.id
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::Path,
+};
+
+use alpacka::{
+    config,
+    smith::{enums::Loaders, Git},
+};
+use error_stack::{Context, IntoReport, Result, ResultExt};
+
+#[derive(Debug)]
+pub enum Error {
+    FormattingError,
+    WriteError,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::FormattingError => "Failed to format schema",
+            Self::WriteError => "Failed to write schema",
+        })
+    }
+}
+
+impl Context for Error {}
+
+/// Print the JSON schema for the config format, or write it to a file
+///
+/// # Errors
+/// Errors if the schema cannot be formatted, or if the output file cannot be written.
+pub fn schema(output: Option<&Path>) -> Result<(), Error> {
+    let smiths: Vec<Loaders> = vec![Loaders::Git(Git::new())];
+
+    let json = serde_json::to_string_pretty(&config::schema(&smiths))
+        .into_report()
+        .change_context(Error::FormattingError)?;
+
+    match output {
+        Some(path) => std::fs::write(path, json)
+            .into_report()
+            .attach_printable_lazy(|| format!("Schema file path: {}", path.display()))
+            .change_context(Error::WriteError),
+        None => {
+            println!("{json}");
+            Ok(())
+        }
+    }
+}
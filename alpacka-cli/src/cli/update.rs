@@ -0,0 +1,195 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::Path,
+    time::Instant,
+};
+
+use alpacka::{
+    condition::ConditionContext,
+    manifest::{
+        add_to_generations, get_current, get_installed, GenerationHash, Manifest, Metadata,
+    },
+    plan::plan,
+    smith::{enums::Loaders, Git},
+    update::update as update_manifest,
+};
+use error_stack::{Context, IntoReport, Report, Result, ResultExt};
+use rkyv::{Deserialize, Infallible};
+use serde_json::json;
+use tracing::info;
+
+use super::clap::ListGenerationsFormatMethod;
+use crate::cli::{
+    get_generations_from_file,
+    install::{load_config_with_snapshot, load_manifest, print_plan, remove_stale},
+    peek_generations_file, read_generations_file, short,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    Load,
+    NoGeneration,
+    Update,
+    Write,
+    Install,
+    Format,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Load => "Failed to load generation",
+            Self::NoGeneration => {
+                "No generation exists for the current config, run `alpacka install` first"
+            }
+            Self::Update => "Failed to update packages",
+            Self::Write => "Failed to write generations file",
+            Self::Install => "Failed to install updated packages",
+            Self::Format => "Failed to format output",
+        })
+    }
+}
+
+impl Context for Error {}
+
+/// Update the floating packages of the current config, or only the named ones, and install the new generation
+///
+/// With `dry_run`, the plan of what installing the updates would change is printed instead, and nothing is written.
+/// New commits are only ever fetched into temporary repositories, so the plugin checkouts are not changed either
+///
+/// # Errors
+/// Errors if the config or generations file cannot be loaded, if there is no generation for the config, if the
+/// packages cannot be updated, or if the new generation cannot be written or installed.
+///
+/// # Panics
+/// Cannot panic, as the only error that can occur when deserializing the manifest is [`Infallible`]
+pub fn update(
+    config_path: &Path,
+    data_path: &Path,
+    profile: Option<&str>,
+    names: &[String],
+    accept_breaking: bool,
+    dry_run: bool,
+    format_style: &ListGenerationsFormatMethod,
+) -> Result<(), Error> {
+    let (config, snapshot) =
+        load_config_with_snapshot(config_path, profile).change_context(Error::Load)?;
+    let conditions = config.evaluate_conditions(&ConditionContext::detect());
+    let config_hash = config.stable_hash(&conditions);
+
+    let generations_path = data_path.join("generations.rkyv");
+    let read = if dry_run {
+        peek_generations_file
+    } else {
+        read_generations_file
+    };
+    let generations_file = read(&generations_path, Some((config.legacy_hash(), config_hash)))
+        .change_context(Error::Load)?;
+
+    let generations = get_generations_from_file(&generations_file)
+        .map_err(|_| Error::Load)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!("Generations file path: {}", generations_path.display())
+        })?;
+
+    let (_, current) =
+        get_current(generations, config_hash).ok_or_else(|| Report::new(Error::NoGeneration))?;
+    let manifest: Manifest = current.deserialize(&mut Infallible).unwrap();
+    let installed: Manifest = get_installed(generations)
+        .unwrap_or(current)
+        .deserialize(&mut Infallible)
+        .unwrap();
+
+    let smiths: Vec<Loaders> = vec![Loaders::Git(Git::new())];
+    let started = Instant::now();
+    let (manifest, updates) = update_manifest(manifest, &smiths, names, data_path, accept_breaking)
+        .change_context(Error::Update)?;
+    let metadata = Metadata {
+        resolution_duration_ms: u64::try_from(started.elapsed().as_millis()).ok(),
+        ..Metadata::detect(config_path)
+    };
+
+    let updated = updates.iter().filter(|update| !update.held_back).count();
+    let actions = dry_run.then(|| {
+        if updated == 0 {
+            Vec::new()
+        } else {
+            plan(Some(&installed), &manifest, data_path)
+        }
+    });
+
+    match format_style {
+        ListGenerationsFormatMethod::Human => {
+            if updates.is_empty() {
+                println!("Everything is up to date");
+            }
+
+            for update in &updates {
+                println!(
+                    "{}: {} -> {}{}",
+                    update.name,
+                    short(&update.old),
+                    short(&update.new),
+                    if update.held_back {
+                        " (held back, run with --accept-breaking to update)"
+                    } else {
+                        ""
+                    }
+                );
+
+                for subject in &update.breaking {
+                    println!("  breaking: {subject}");
+                }
+            }
+
+            if let Some(actions) = &actions {
+                print_plan(actions, data_path);
+            }
+        }
+        ListGenerationsFormatMethod::Json => {
+            let json = match &actions {
+                Some(actions) => serde_json::to_string(&json!({
+                    "updates": updates,
+                    "plan": actions,
+                })),
+                None => serde_json::to_string(&updates),
+            }
+            .into_report()
+            .change_context(Error::Format)?;
+
+            println!("{json}");
+        }
+    }
+
+    if dry_run || updated == 0 {
+        return Ok(());
+    }
+
+    let mut new_generations =
+        add_to_generations(generations, config_hash, manifest, metadata, Some(snapshot));
+
+    new_generations
+        .save_to_file(&generations_path)
+        .into_report()
+        .attach_printable_lazy(|| format!("Generations file path: {}", generations_path.display()))
+        .change_context(Error::Write)?;
+
+    info!("Updated {updated} packages, installing");
+
+    let generation = new_generations
+        .get_latest_generation_number(config_hash)
+        .ok_or_else(|| Report::new(Error::NoGeneration))?;
+    let hash = GenerationHash(config_hash, generation);
+
+    let manifest = &new_generations.generations[&hash].manifest;
+    load_manifest(&smiths, manifest, data_path).change_context(Error::Install)?;
+    remove_stale(Some(&installed), manifest, data_path).change_context(Error::Install)?;
+
+    new_generations.active = Some(hash);
+    new_generations
+        .save_to_file(&generations_path)
+        .into_report()
+        .attach_printable_lazy(|| format!("Generations file path: {}", generations_path.display()))
+        .change_context(Error::Write)
+}
@@ -3,11 +3,24 @@
 mod cli;
 
 use cli::{
-    clap::{Cli, ListGenerationsFormatMethod},
+    changelog::changelog,
+    clap::{
+        Cli, ExportFormatMethod, GenerationCommand, ImportFormatMethod, ListGenerationsFormatMethod,
+    },
+    diff::diff,
+    edit::{add, remove},
+    export::export,
+    generation::{restore_config, show_config},
+    import::import,
     install::install,
     list_generations::list_generations,
+    outdated::outdated,
+    rollback::rollback,
+    schema::schema,
+    update::update,
 };
 
+use alpacka::{config::profile_data_path, export::ExportFormat, import::ImportFormat};
 use clap::Parser;
 use error_stack::{Context, Report, ResultExt};
 
@@ -50,11 +63,100 @@ fn main() -> error_stack::Result<(), MainError> {
         .init();
 
     match Cli::parse() {
-        Cli::Install { path, data_dir } => cli_install(path, data_dir),
+        Cli::Install {
+            path,
+            data_dir,
+            profile,
+            message,
+            dry_run,
+        } => cli_install(
+            path,
+            data_dir,
+            profile.as_deref(),
+            message.as_deref(),
+            dry_run,
+        ),
+        Cli::Update {
+            names,
+            path,
+            data_dir,
+            profile,
+            accept_breaking,
+            dry_run,
+            format_style,
+        } => cli_update(
+            &names,
+            path,
+            data_dir,
+            profile.as_deref(),
+            accept_breaking,
+            dry_run,
+            format_style,
+        ),
+        Cli::Rollback {
+            to,
+            path,
+            data_dir,
+            profile,
+        } => cli_rollback(to, path, data_dir, profile.as_deref()),
+        Cli::Outdated {
+            path,
+            data_dir,
+            format_style,
+            profile,
+        } => cli_outdated(path, data_dir, format_style, profile.as_deref()),
+        Cli::Changelog {
+            names,
+            from,
+            to,
+            path,
+            data_dir,
+            format_style,
+            profile,
+        } => cli_changelog(
+            &names,
+            from,
+            to,
+            path,
+            data_dir,
+            format_style,
+            profile.as_deref(),
+        ),
         Cli::ListGenerations {
             data_dir,
             format_style,
-        } => cli_list_generations(data_dir, format_style),
+            profile,
+        } => cli_list_generations(data_dir, format_style, profile.as_deref()),
+        Cli::Diff {
+            from,
+            to,
+            data_dir,
+            format_style,
+            profile,
+        } => cli_diff(from, to, data_dir, format_style, profile.as_deref()),
+        Cli::Generation { command } => cli_generation(command),
+        Cli::Add {
+            name,
+            version,
+            optional,
+            path,
+        } => cli_add(path, &name, version, optional),
+        Cli::Remove { name, path } => cli_remove(path, &name),
+        Cli::Import {
+            format,
+            files,
+            plugin_dir,
+            path,
+            data_dir,
+        } => cli_import(format, &files, plugin_dir, path, data_dir),
+        Cli::Export {
+            format,
+            output,
+            path,
+            data_dir,
+            profile,
+        } => cli_export(format, output, path, data_dir, profile.as_deref()),
+        Cli::Schema { output } => cli_schema(output),
     }?;
 
     Ok(())
@@ -63,8 +165,9 @@ fn main() -> error_stack::Result<(), MainError> {
 fn cli_list_generations(
     data_dir: Option<PathBuf>,
     format_style: Option<ListGenerationsFormatMethod>,
+    profile: Option<&str>,
 ) -> Result<(), Report<MainError>> {
-    let data_path = get_data_path(data_dir);
+    let data_path = get_data_path(data_dir, profile);
 
     list_generations(
         &data_path,
@@ -73,28 +176,228 @@ fn cli_list_generations(
     .change_context(MainError)
 }
 
-fn cli_install(path: Option<PathBuf>, data_dir: Option<PathBuf>) -> Result<(), Report<MainError>> {
-    let data_path = get_data_path(data_dir);
+fn cli_diff(
+    from: u64,
+    to: u64,
+    data_dir: Option<PathBuf>,
+    format_style: Option<ListGenerationsFormatMethod>,
+    profile: Option<&str>,
+) -> Result<(), Report<MainError>> {
+    diff(
+        &get_data_path(data_dir, profile),
+        from,
+        to,
+        &format_style.unwrap_or(ListGenerationsFormatMethod::Human),
+    )
+    .change_context(MainError)
+}
+
+fn cli_generation(command: GenerationCommand) -> Result<(), Report<MainError>> {
+    match command {
+        GenerationCommand::ShowConfig {
+            id,
+            data_dir,
+            profile,
+        } => show_config(&get_data_path(data_dir, profile.as_deref()), id),
+        GenerationCommand::RestoreConfig {
+            id,
+            path,
+            data_dir,
+            profile,
+        } => restore_config(
+            &get_config_path(path),
+            &get_data_path(data_dir, profile.as_deref()),
+            id,
+        ),
+    }
+    .change_context(MainError)
+}
+
+fn cli_schema(output: Option<PathBuf>) -> Result<(), Report<MainError>> {
+    schema(output.as_deref()).change_context(MainError)
+}
+
+fn cli_add(
+    path: Option<PathBuf>,
+    name: &str,
+    version: Option<String>,
+    optional: bool,
+) -> Result<(), Report<MainError>> {
+    add(&get_config_path(path), name, version, optional).change_context(MainError)
+}
+
+fn cli_remove(path: Option<PathBuf>, name: &str) -> Result<(), Report<MainError>> {
+    remove(&get_config_path(path), name).change_context(MainError)
+}
+
+fn cli_import(
+    format: ImportFormatMethod,
+    files: &[PathBuf],
+    plugin_dirs: Vec<PathBuf>,
+    path: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+) -> Result<(), Report<MainError>> {
+    let format = match format {
+        ImportFormatMethod::Lazy => ImportFormat::LazyLock,
+        ImportFormatMethod::Packer => ImportFormat::PackerSnapshot,
+        ImportFormatMethod::VimPlug => ImportFormat::VimPlug,
+    };
+
+    let plugin_dirs = if plugin_dirs.is_empty() {
+        format.default_plugin_dirs(&get_nvim_data_path())
+    } else {
+        plugin_dirs
+    };
+
+    import(
+        format,
+        files,
+        &plugin_dirs,
+        &get_config_path(path),
+        &get_data_path(data_dir, None),
+    )
+    .change_context(MainError)
+}
+
+fn cli_export(
+    format: ExportFormatMethod,
+    output: Option<PathBuf>,
+    path: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    profile: Option<&str>,
+) -> Result<(), Report<MainError>> {
+    let format = match format {
+        ExportFormatMethod::Lazy => ExportFormat::LazyLock,
+        ExportFormatMethod::VimPack => ExportFormat::VimPack,
+        ExportFormatMethod::Nix => ExportFormat::Nix,
+    };
 
-    let config_path = path.unwrap_or_else(|| {
+    export(
+        format,
+        &get_config_path(path),
+        &get_data_path(data_dir, profile),
+        profile,
+        output.as_deref(),
+    )
+    .change_context(MainError)
+}
+
+fn cli_install(
+    path: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    profile: Option<&str>,
+    message: Option<&str>,
+    dry_run: bool,
+) -> Result<(), Report<MainError>> {
+    let data_path = get_data_path(data_dir, profile);
+
+    let config_path = get_config_path(path);
+
+    install(config_path, &data_path, profile, message, dry_run).change_context(MainError)
+}
+
+fn cli_update(
+    names: &[String],
+    path: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    profile: Option<&str>,
+    accept_breaking: bool,
+    dry_run: bool,
+    format_style: Option<ListGenerationsFormatMethod>,
+) -> Result<(), Report<MainError>> {
+    update(
+        &get_config_path(path),
+        &get_data_path(data_dir, profile),
+        profile,
+        names,
+        accept_breaking,
+        dry_run,
+        &format_style.unwrap_or(ListGenerationsFormatMethod::Human),
+    )
+    .change_context(MainError)
+}
+
+fn cli_rollback(
+    to: Option<u64>,
+    path: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    profile: Option<&str>,
+) -> Result<(), Report<MainError>> {
+    rollback(
+        &get_config_path(path),
+        &get_data_path(data_dir, profile),
+        profile,
+        to,
+    )
+    .change_context(MainError)
+}
+
+fn cli_outdated(
+    path: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    format_style: Option<ListGenerationsFormatMethod>,
+    profile: Option<&str>,
+) -> Result<(), Report<MainError>> {
+    outdated(
+        &get_config_path(path),
+        &get_data_path(data_dir, profile),
+        profile,
+        &format_style.unwrap_or(ListGenerationsFormatMethod::Human),
+    )
+    .change_context(MainError)
+}
+
+fn cli_changelog(
+    names: &[String],
+    from: Option<u64>,
+    to: Option<u64>,
+    path: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    format_style: Option<ListGenerationsFormatMethod>,
+    profile: Option<&str>,
+) -> Result<(), Report<MainError>> {
+    changelog(
+        &get_config_path(path),
+        &get_data_path(data_dir, profile),
+        profile,
+        from,
+        to,
+        names,
+        &format_style.unwrap_or(ListGenerationsFormatMethod::Human),
+    )
+    .change_context(MainError)
+}
+
+fn get_config_path(path: Option<PathBuf>) -> PathBuf {
+    path.unwrap_or_else(|| {
         let config_dir = std::env::var_os("XDG_CONFIG_HOME")
             .and_then(dirs_sys::is_absolute_path)
             .or_else(|| dirs_sys::home_dir().map(|h| h.join(".config")));
 
-        config_dir.map(|cd| cd.join("nvim/packages.json")).unwrap()
-    });
+        let json_config = config_dir.map(|cd| cd.join("nvim/packages.json")).unwrap();
+        let lua_config = json_config.with_extension("lua");
 
-    install(config_path, &data_path).change_context(MainError)
+        if !json_config.exists() && lua_config.exists() {
+            lua_config
+        } else {
+            json_config
+        }
+    })
 }
 
-fn get_data_path(data_dir: Option<PathBuf>) -> PathBuf {
-    data_dir.unwrap_or_else(|| {
-        let data_dir = std::env::var_os("XDG_DATA_HOME")
-            .and_then(dirs_sys::is_absolute_path)
-            .or_else(|| dirs_sys::home_dir().map(|h| h.join(".local/share")));
+fn get_data_path(data_dir: Option<PathBuf>, profile: Option<&str>) -> PathBuf {
+    let data_path = data_dir.unwrap_or_else(|| get_nvim_data_path().join("site/pack/alpacka/"));
 
-        data_dir
-            .map(|dd| dd.join("nvim/site/pack/alpacka/"))
-            .unwrap()
-    })
+    match profile {
+        Some(profile) => profile_data_path(&data_path, profile),
+        None => data_path,
+    }
+}
+
+fn get_nvim_data_path() -> PathBuf {
+    let data_dir = std::env::var_os("XDG_DATA_HOME")
+        .and_then(dirs_sys::is_absolute_path)
+        .or_else(|| dirs_sys::home_dir().map(|h| h.join(".local/share")));
+
+    data_dir.map(|dd| dd.join("nvim")).unwrap()
 }
@@ -0,0 +1,80 @@
+//! A module which detects breaking changes
+//!
+//! Commits are breaking when their message follows [conventional commits](https://www.conventionalcommits.org) and
+//! marks a breaking change, either with a `!` before the colon of the header (`feat!: ...`, `fix(api)!: ...`), or with
+//! a `BREAKING CHANGE:` footer. Version tags are breaking when they bump the major version, or the minor version
+//! while the major version is 0.
+
+use semver::Version;
+
+/// Check if a commit message marks a breaking change
+#[must_use]
+pub fn is_breaking_commit(message: &str) -> bool {
+    let mut lines = message.lines();
+
+    let header_is_breaking = lines.next().is_some_and(|header| {
+        header.split_once(':').is_some_and(|(prefix, _)| {
+            prefix.strip_suffix('!').is_some_and(|kind| {
+                // the type can be followed by a scope in parentheses
+                let kind = kind
+                    .strip_suffix(')')
+                    .and_then(|kind| kind.split_once('('))
+                    .map_or(kind, |(kind, _)| kind);
+
+                !kind.is_empty() && kind.chars().all(|c| c.is_ascii_alphabetic())
+            })
+        })
+    });
+
+    header_is_breaking
+        || lines.any(|line| {
+            line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")
+        })
+}
+
+/// Parse a tag as a semver version, allowing a leading `v`
+#[must_use]
+pub fn tag_version(tag: &str) -> Option<Version> {
+    Version::parse(tag.strip_prefix('v').unwrap_or(tag)).ok()
+}
+
+/// Check if going from one version to another is a breaking change
+#[must_use]
+pub const fn is_major_bump(from: &Version, to: &Version) -> bool {
+    if from.major == 0 && to.major == 0 {
+        to.minor > from.minor
+    } else {
+        to.major > from.major
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_breaking_commit() {
+        assert!(is_breaking_commit("feat!: remove setup()"));
+        assert!(is_breaking_commit("fix(api)!: rename options"));
+        assert!(is_breaking_commit(
+            "feat: new options\n\nBREAKING CHANGE: `opts.old` was removed"
+        ));
+        assert!(is_breaking_commit("refactor: x\n\nBREAKING-CHANGE: y"));
+
+        assert!(!is_breaking_commit("feat: add a command"));
+        assert!(!is_breaking_commit("Merge branch 'main'!: not a type"));
+        assert!(!is_breaking_commit(
+            "docs: mention BREAKING CHANGE: in the readme"
+        ));
+    }
+
+    #[test]
+    fn test_is_major_bump() {
+        let version = |tag| tag_version(tag).unwrap();
+
+        assert!(is_major_bump(&version("v1.4.0"), &version("v2.0.0")));
+        assert!(is_major_bump(&version("0.1.4"), &version("0.2.0")));
+        assert!(!is_major_bump(&version("v1.4.0"), &version("v1.5.0")));
+        assert!(!is_major_bump(&version("0.1.4"), &version("0.1.5")));
+    }
+}
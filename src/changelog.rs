@@ -0,0 +1,164 @@
+//! A module which lists the commits between two generations
+//!
+//! Only plugins which are in both manifests, at different revisions, have a changelog. The commits are read from the
+//! installed checkouts of the plugins, or fetched into a temporary repository when a plugin has no checkout or its
+//! checkout is missing a commit, so the checkouts are never changed.
+
+use crate::{
+    manifest::Manifest,
+    smith::{enums::Loaders, Commit},
+};
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::{fmt::Display, path::Path};
+
+#[derive(Debug)]
+/// An error that can occur when creating a changelog
+pub enum ChangelogError {
+    /// The package is not in the newer manifest
+    UnknownPackage(String),
+    /// The commits of a package could not be read
+    Commits,
+}
+
+impl Display for ChangelogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownPackage(name) => write!(f, "Package {name} is not installed"),
+            Self::Commits => f.write_str("Failed to read the commits of a package"),
+        }
+    }
+}
+
+impl error_stack::Context for ChangelogError {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// The commits of a plugin between two generations
+pub struct PluginChangelog {
+    /// The name of the package, as used in the config
+    pub name: String,
+    /// The revision the plugin is at in the older generation
+    pub from: String,
+    /// The revision the plugin is at in the newer generation
+    pub to: String,
+    /// The commits after `from` up to and including `to`, newest first
+    pub commits: Vec<Commit>,
+}
+
+/// List the commits of every plugin which changed revision between two manifests, or only of the named plugins
+///
+/// Plugins are read from their checkouts inside of `data_path`, where the newer manifest installs them.
+///
+/// # Errors
+/// This function will return an error if a named package is not in the newer manifest, or if the commits of a plugin
+/// cannot be read
+pub fn changelog(
+    from: &Manifest,
+    to: &Manifest,
+    smiths: &[Loaders],
+    data_path: &Path,
+    names: &[String],
+) -> Result<Vec<PluginChangelog>, ChangelogError> {
+    if let Some(name) = names
+        .iter()
+        .find(|name| !to.plugins.iter().any(|plugin| plugin.is_named(name)))
+    {
+        return Err(Report::new(ChangelogError::UnknownPackage(name.clone())));
+    }
+
+    let changed = to
+        .plugins
+        .iter()
+        .filter(|plugin| names.is_empty() || names.iter().any(|name| plugin.is_named(name)))
+        .filter_map(|plugin| {
+            from.plugins
+                .iter()
+                .find(|old| old.unresolved_name == plugin.unresolved_name)
+                .filter(|old| old.loader_data.revision() != plugin.loader_data.revision())
+                .map(|old| (old, plugin))
+        })
+        .collect::<Vec<_>>();
+
+    changed
+        .into_par_iter()
+        .map(|(old, plugin)| {
+            let smith = smiths
+                .iter()
+                .find(|smith| smith.name() == plugin.smith)
+                .ok_or(ChangelogError::Commits)
+                .into_report()
+                .attach_printable_lazy(|| format!("Smith {} not found", plugin.smith))?;
+
+            let commits = smith
+                .get_change_log(
+                    Some(&old.loader_data),
+                    &plugin.loader_data,
+                    &plugin.install_path(data_path),
+                )
+                .attach_printable_lazy(|| format!("Package name: {}", plugin.unresolved_name))
+                .change_context(ChangelogError::Commits)?;
+
+            Ok(PluginChangelog {
+                name: plugin.unresolved_name.clone(),
+                from: old.loader_data.revision().to_string(),
+                to: plugin.loader_data.revision().to_string(),
+                commits,
+            })
+        })
+        .collect()
+}
+
+/// Format a time in seconds since the Unix epoch as a `YYYY-MM-DD` date, in UTC
+#[must_use]
+pub fn date(time: i64) -> String {
+    // the civil-from-days algorithm, see https://howardhinnant.github.io/date_algorithms.html
+    let days = time.div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Format a time in seconds since the Unix epoch as a `YYYY-MM-DD HH:MM` date and time, in UTC
+#[must_use]
+pub fn date_time(time: i64) -> String {
+    let seconds = time.rem_euclid(86_400);
+
+    format!(
+        "{} {:02}:{:02}",
+        date(time),
+        seconds / 3600,
+        seconds % 3600 / 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(951_782_400), "2000-02-29");
+        assert_eq!(date(1_700_000_000), "2023-11-14");
+    }
+
+    #[test]
+    fn test_date_time() {
+        assert_eq!(date_time(0), "1970-01-01 00:00");
+        assert_eq!(date_time(951_782_399), "2000-02-28 23:59");
+        assert_eq!(date_time(1_700_000_000), "2023-11-14 22:13");
+    }
+}
@@ -0,0 +1,260 @@
+//! A module which contains conditions for loading packages
+//!
+//! A package with a `when` clause is only loaded if every check in the clause passes on the current machine.
+
+use bytecheck::CheckBytes;
+use rkyv::Archive;
+use schemars::JsonSchema;
+use semver::{Version, VersionReq};
+use serde::{de::Error as DeError, Deserialize, Deserializer, Serialize};
+use std::process::Command;
+
+#[derive(
+    Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Hash, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[serde(deny_unknown_fields)]
+/// The conditions under which a package is loaded
+///
+/// Every check has to pass for the package to be loaded. Checks which take a list pass if any of the values match.
+pub struct Condition {
+    /// The operating systems to load the package on, e.g. `linux`, `macos` or `windows`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub os: Vec<String>,
+    /// The CPU architectures to load the package on, e.g. `x86_64` or `aarch64`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arch: Vec<String>,
+    /// The hostnames to load the package on
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hostname: Vec<String>,
+    /// Environment variables, one of which has to be set, either as `NAME` or as `NAME=value` to require a specific value
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub env: Vec<String>,
+    /// Executables which all have to be on the `PATH`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub executable: Vec<String>,
+    /// A semver range the Neovim version has to be in, e.g. `>=0.9.0`
+    #[serde(
+        default,
+        deserialize_with = "deserialize_neovim_range",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub neovim: Option<String>,
+}
+
+/// Deserialize a Neovim version range, rejecting malformed ranges instead of never loading the package
+fn deserialize_neovim_range<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let range = Option::<String>::deserialize(deserializer)?;
+
+    if let Some(range) = &range {
+        VersionReq::parse(range).map_err(|error| {
+            D::Error::custom(format!("invalid Neovim version range `{range}`, {error}"))
+        })?;
+    }
+
+    Ok(range)
+}
+
+#[derive(Debug, Clone)]
+/// Information about the current machine, which conditions are evaluated against
+pub struct ConditionContext {
+    /// The operating system, as found in [`std::env::consts::OS`]
+    pub os: String,
+    /// The CPU architecture, as found in [`std::env::consts::ARCH`]
+    pub arch: String,
+    /// The hostname, if it could be found
+    pub hostname: Option<String>,
+    /// The Neovim version, if Neovim could be found
+    pub neovim_version: Option<Version>,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+#[archive_attr(derive(CheckBytes, Debug))]
+/// The outcome of a single check in a package's condition
+pub struct ConditionOutcome {
+    /// The unresolved name of the package the condition belongs to
+    pub package: String,
+    /// A description of the check, e.g. `os = linux`
+    pub check: String,
+    /// Whether the check passed
+    pub passed: bool,
+}
+
+impl ConditionContext {
+    /// Detect the context of the current machine
+    ///
+    /// The Neovim version is found by running `nvim --version`
+    #[must_use]
+    pub fn detect() -> Self {
+        Self {
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            hostname: hostname::get()
+                .ok()
+                .and_then(|hostname| hostname.into_string().ok()),
+            neovim_version: detect_neovim_version(),
+        }
+    }
+}
+
+/// Get the version of the Neovim on the `PATH`
+///
+/// Pre-release information is dropped, so that development builds match ranges such as `>=0.9.0`
+fn detect_neovim_version() -> Option<Version> {
+    let output = Command::new("nvim").arg("--version").output().ok()?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+
+    // the first line looks like `NVIM v0.9.0` or `NVIM v0.10.0-dev-1234+g0123abcd`
+    let version = stdout.lines().next()?.strip_prefix("NVIM v")?;
+    let mut version = Version::parse(version).ok()?;
+
+    version.pre = semver::Prerelease::EMPTY;
+    version.build = semver::BuildMetadata::EMPTY;
+
+    Some(version)
+}
+
+/// Check if an environment variable check, in the form `NAME` or `NAME=value`, passes
+fn env_matches(check: &str) -> bool {
+    match check.split_once('=') {
+        Some((name, value)) => std::env::var(name).is_ok_and(|var| var == value),
+        None => std::env::var_os(check).is_some(),
+    }
+}
+
+impl Condition {
+    /// Evaluate every check in this condition
+    ///
+    /// `package` is the unresolved name of the package this condition belongs to
+    #[must_use]
+    pub fn evaluate(&self, package: &str, context: &ConditionContext) -> Vec<ConditionOutcome> {
+        let mut outcomes = Vec::new();
+        let mut record = |check: String, passed: bool| {
+            outcomes.push(ConditionOutcome {
+                package: package.to_string(),
+                check,
+                passed,
+            });
+        };
+
+        if !self.os.is_empty() {
+            record(
+                format!("os = {}", self.os.join(" | ")),
+                self.os.contains(&context.os),
+            );
+        }
+
+        if !self.arch.is_empty() {
+            record(
+                format!("arch = {}", self.arch.join(" | ")),
+                self.arch.contains(&context.arch),
+            );
+        }
+
+        if !self.hostname.is_empty() {
+            record(
+                format!("hostname = {}", self.hostname.join(" | ")),
+                context
+                    .hostname
+                    .as_ref()
+                    .is_some_and(|hostname| self.hostname.contains(hostname)),
+            );
+        }
+
+        if !self.env.is_empty() {
+            record(
+                format!("env = {}", self.env.join(" | ")),
+                self.env.iter().any(|check| env_matches(check)),
+            );
+        }
+
+        for executable in &self.executable {
+            record(
+                format!("executable = {executable}"),
+                which::which(executable).is_ok(),
+            );
+        }
+
+        if let Some(range) = &self.neovim {
+            let passed = VersionReq::parse(range).is_ok_and(|range| {
+                context
+                    .neovim_version
+                    .as_ref()
+                    .is_some_and(|version| range.matches(version))
+            });
+
+            record(format!("neovim = {range}"), passed);
+        }
+
+        outcomes
+    }
+
+    /// Check if every check in this condition passes
+    #[must_use]
+    pub fn passes(&self, package: &str, context: &ConditionContext) -> bool {
+        self.evaluate(package, context)
+            .iter()
+            .all(|outcome| outcome.passed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> ConditionContext {
+        ConditionContext {
+            os: "linux".to_string(),
+            arch: "x86_64".to_string(),
+            hostname: Some("laptop".to_string()),
+            neovim_version: Some(Version::new(0, 9, 1)),
+        }
+    }
+
+    #[test]
+    fn test_condition_passes() {
+        let condition = Condition {
+            os: vec!["linux".to_string(), "macos".to_string()],
+            hostname: vec!["laptop".to_string()],
+            neovim: Some(">=0.9.0".to_string()),
+            ..Condition::default()
+        };
+
+        assert!(condition.passes("github:a/b", &context()));
+    }
+
+    #[test]
+    fn test_condition_fails() {
+        let condition = Condition {
+            arch: vec!["aarch64".to_string()],
+            neovim: Some(">=0.10.0".to_string()),
+            ..Condition::default()
+        };
+
+        let outcomes = condition.evaluate("github:a/b", &context());
+
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes.iter().all(|outcome| !outcome.passed));
+    }
+
+    #[test]
+    fn test_malformed_neovim_range_is_rejected() {
+        assert!(serde_json::from_str::<Condition>(r#"{ "neovim": ">= 0.9 <" }"#).is_err());
+        assert!(serde_json::from_str::<Condition>(r#"{ "neovim": ">=0.9, <0.11" }"#).is_ok());
+    }
+}
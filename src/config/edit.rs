@@ -0,0 +1,506 @@
+//! Programmatic editing of JSON config files
+//!
+//! Edits are made to the text of the config file, so everything which is not edited keeps its key order and formatting.
+//! New entries are indented like the entries around them.
+
+use super::Config;
+use crate::package::{validate_version, Config as ConfigPackage};
+use error_stack::{Context, IntoReport, Report, Result, ResultExt};
+use serde::Serialize;
+use serde_json::{ser::PrettyFormatter, Serializer, Value};
+use std::{fmt::Display, ops::Range, path::Path};
+
+#[derive(Debug)]
+/// An error that can occur when editing a config file
+pub enum EditError {
+    /// The config file is not valid JSON, or does not have the expected structure
+    Parse,
+    /// The package does not exist in the config
+    NotFound(String),
+    /// The package already exists in the config
+    AlreadyExists(String),
+    /// The new value is not valid
+    InvalidValue,
+}
+
+impl Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse => f.write_str("Failed to parse config file for editing"),
+            Self::NotFound(name) => write!(f, "Package {name} is not in the config"),
+            Self::AlreadyExists(name) => write!(f, "Package {name} is already in the config"),
+            Self::InvalidValue => f.write_str("Invalid value for config field"),
+        }
+    }
+}
+
+impl Context for EditError {}
+
+/// A member of a JSON object, as byte ranges into the document
+#[derive(Debug)]
+struct Member {
+    /// The decoded key
+    key: String,
+    /// The range of the key, including its quotes
+    key_span: Range<usize>,
+    /// The range of the value
+    value_span: Range<usize>,
+}
+
+/// A JSON object, as byte ranges into the document
+#[derive(Debug)]
+struct Object {
+    /// The index of the opening brace
+    open: usize,
+    /// The index of the closing brace
+    close: usize,
+    members: Vec<Member>,
+}
+
+/// A minimal scanner which finds the byte ranges of JSON values, without building a tree
+struct Scanner<'a> {
+    source: &'a str,
+}
+
+impl Scanner<'_> {
+    fn skip_whitespace(&self, mut index: usize) -> usize {
+        while self
+            .source
+            .as_bytes()
+            .get(index)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            index += 1;
+        }
+
+        index
+    }
+
+    fn expect(&self, index: usize, byte: u8) -> Option<usize> {
+        (self.source.as_bytes().get(index) == Some(&byte)).then_some(index + 1)
+    }
+
+    /// Find the end of the string starting at `index`
+    fn string_end(&self, index: usize) -> Option<usize> {
+        let bytes = self.source.as_bytes();
+        let mut index = self.expect(index, b'"')?;
+
+        loop {
+            match bytes.get(index)? {
+                b'\\' => index += 2,
+                b'"' => return Some(index + 1),
+                _ => index += 1,
+            }
+        }
+    }
+
+    /// Find the end of the value starting at `index`
+    fn value_end(&self, index: usize) -> Option<usize> {
+        let bytes = self.source.as_bytes();
+
+        match bytes.get(index)? {
+            b'"' => self.string_end(index),
+            b'{' => self.object(index).map(|object| object.close + 1),
+            b'[' => {
+                let mut index = self.skip_whitespace(index + 1);
+
+                if bytes.get(index) == Some(&b']') {
+                    return Some(index + 1);
+                }
+
+                loop {
+                    index = self.skip_whitespace(self.value_end(index)?);
+
+                    match bytes.get(index)? {
+                        b',' => index = self.skip_whitespace(index + 1),
+                        b']' => return Some(index + 1),
+                        _ => return None,
+                    }
+                }
+            }
+            _ => {
+                let length = bytes[index..]
+                    .iter()
+                    .position(|byte| {
+                        matches!(byte, b',' | b'}' | b']') || byte.is_ascii_whitespace()
+                    })
+                    .unwrap_or(bytes.len() - index);
+
+                Some(index + length)
+            }
+        }
+    }
+
+    /// Scan the object starting at `open`
+    fn object(&self, open: usize) -> Option<Object> {
+        let bytes = self.source.as_bytes();
+        let mut index = self.skip_whitespace(self.expect(open, b'{')?);
+        let mut members = Vec::new();
+
+        if bytes.get(index) == Some(&b'}') {
+            return Some(Object {
+                open,
+                close: index,
+                members,
+            });
+        }
+
+        loop {
+            let key_end = self.string_end(index)?;
+            let key = serde_json::from_str(&self.source[index..key_end]).ok()?;
+            let value_start =
+                self.skip_whitespace(self.expect(self.skip_whitespace(key_end), b':')?);
+            let value_end = self.value_end(value_start)?;
+
+            members.push(Member {
+                key,
+                key_span: index..key_end,
+                value_span: value_start..value_end,
+            });
+
+            index = self.skip_whitespace(value_end);
+
+            match bytes.get(index)? {
+                b',' => index = self.skip_whitespace(index + 1),
+                b'}' => {
+                    return Some(Object {
+                        open,
+                        close: index,
+                        members,
+                    })
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// A JSON config file which can be edited while keeping its formatting
+#[derive(Debug, Clone)]
+pub struct ConfigDocument {
+    source: String,
+}
+
+impl ConfigDocument {
+    /// Create a document from the contents of a JSON config file
+    ///
+    /// # Errors
+    /// This function will return an error if the source is not a valid config
+    pub fn new(source: String) -> Result<Self, EditError> {
+        let document = Self { source };
+        document.validate()?;
+
+        Ok(document)
+    }
+
+    /// Get the edited contents of the config file
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Parse the document as a config, to check that edits produced a valid config
+    ///
+    /// # Errors
+    /// This function will return an error if the document is not a valid config
+    pub fn validate(&self) -> Result<Config, EditError> {
+        Config::from_json(&self.source, Path::new("<edited config>"))
+            .change_context(EditError::Parse)
+    }
+
+    /// Add a package to the config
+    ///
+    /// # Errors
+    /// This function will return an error if the package already exists
+    pub fn add_package(&mut self, name: &str, package: &ConfigPackage) -> Result<(), EditError> {
+        let packages = self.object(&["packages"])?;
+
+        if packages.members.iter().any(|member| member.key == name) {
+            return Err(Report::new(EditError::AlreadyExists(name.to_string())));
+        }
+
+        let value = serde_json::to_value(package)
+            .into_report()
+            .change_context(EditError::InvalidValue)?;
+
+        self.insert_member(&packages, name, &value)?;
+        self.validate()?;
+
+        Ok(())
+    }
+
+    /// Remove a package from the config
+    ///
+    /// # Errors
+    /// This function will return an error if the package does not exist
+    pub fn remove_package(&mut self, name: &str) -> Result<(), EditError> {
+        let packages = self.object(&["packages"])?;
+
+        self.remove_member(&packages, name)
+            .ok_or_else(|| Report::new(EditError::NotFound(name.to_string())))
+    }
+
+    /// Set the version of a package, or remove it to track the default branch
+    ///
+    /// # Errors
+    /// This function will return an error if the package does not exist, or if the version is malformed
+    pub fn set_version(&mut self, name: &str, version: Option<&str>) -> Result<(), EditError> {
+        if let Some(version) = version {
+            validate_version(version)
+                .map_err(|error| Report::new(EditError::InvalidValue).attach_printable(error))?;
+        }
+
+        self.set_package_field(name, "version", version.map(Value::from))
+    }
+
+    /// Set whether a package is optional
+    ///
+    /// `optional` is removed instead of being set to `false`, since that is the default
+    ///
+    /// # Errors
+    /// This function will return an error if the package does not exist
+    pub fn set_optional(&mut self, name: &str, optional: bool) -> Result<(), EditError> {
+        self.set_package_field(name, "optional", optional.then_some(Value::Bool(true)))
+    }
+
+    /// Set or remove a field of a package
+    fn set_package_field(
+        &mut self,
+        name: &str,
+        field: &str,
+        value: Option<Value>,
+    ) -> Result<(), EditError> {
+        let package = self
+            .object(&["packages", name])
+            .change_context_lazy(|| EditError::NotFound(name.to_string()))?;
+
+        match (
+            package.members.iter().find(|member| member.key == field),
+            value,
+        ) {
+            (Some(member), Some(value)) => {
+                let indent = line_indent(&self.source, member.key_span.start).to_string();
+                let value = self.format_value(&value, &indent)?;
+
+                self.source.replace_range(member.value_span.clone(), &value);
+            }
+            (Some(_), None) => {
+                self.remove_member(&package, field);
+            }
+            (None, Some(value)) => self.insert_member(&package, field, &value)?,
+            (None, None) => {}
+        }
+
+        self.validate()?;
+
+        Ok(())
+    }
+
+    /// Find the object at a path of keys, starting at the root object
+    fn object(&self, path: &[&str]) -> Result<Object, EditError> {
+        let scanner = Scanner {
+            source: &self.source,
+        };
+
+        let mut object = scanner
+            .object(scanner.skip_whitespace(0))
+            .ok_or_else(|| Report::new(EditError::Parse))?;
+
+        for key in path {
+            let member = object
+                .members
+                .iter()
+                .find(|member| member.key == *key)
+                .ok_or_else(|| Report::new(EditError::Parse))
+                .attach_printable_lazy(|| format!("Missing key: {key}"))?;
+
+            object = scanner
+                .object(member.value_span.start)
+                .ok_or_else(|| Report::new(EditError::Parse))
+                .attach_printable_lazy(|| format!("Not an object: {key}"))?;
+        }
+
+        Ok(object)
+    }
+
+    /// Insert a member at the end of an object, formatted like the other members
+    fn insert_member(
+        &mut self,
+        object: &Object,
+        key: &str,
+        value: &Value,
+    ) -> Result<(), EditError> {
+        let key = serde_json::to_string(key)
+            .into_report()
+            .change_context(EditError::InvalidValue)?;
+
+        let parent_indent = line_indent(&self.source, object.open).to_string();
+        let multiline = match object.members.first() {
+            Some(first) => self.source[object.open..first.key_span.start].contains('\n'),
+            None => true,
+        };
+
+        match object.members.last() {
+            Some(last) if multiline => {
+                let indent = line_indent(&self.source, last.key_span.start).to_string();
+                let value = self.format_value(value, &indent)?;
+
+                self.source
+                    .insert_str(last.value_span.end, &format!(",\n{indent}{key}: {value}"));
+            }
+            Some(last) => {
+                let value = serde_json::to_string(value)
+                    .into_report()
+                    .change_context(EditError::InvalidValue)?;
+
+                self.source
+                    .insert_str(last.value_span.end, &format!(", {key}: {value}"));
+            }
+            None => {
+                let indent = format!("{parent_indent}{}", self.indent_unit());
+                let value = self.format_value(value, &indent)?;
+
+                self.source.replace_range(
+                    object.open + 1..object.close,
+                    &format!("\n{indent}{key}: {value}\n{parent_indent}"),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove a member from an object, along with the comma separating it from its neighbours
+    ///
+    /// Returns `None` if the object has no member with that key
+    fn remove_member(&mut self, object: &Object, key: &str) -> Option<()> {
+        let index = object.members.iter().position(|member| member.key == key)?;
+        let member = &object.members[index];
+
+        let range = match (index.checked_sub(1), object.members.get(index + 1)) {
+            (_, Some(next)) => member.key_span.start..next.key_span.start,
+            (Some(previous), None) => {
+                object.members[previous].value_span.end..member.value_span.end
+            }
+            (None, None) => object.open + 1..object.close,
+        };
+
+        self.source.replace_range(range, "");
+
+        Some(())
+    }
+
+    /// Format a value with the indent unit of the document, starting at the given indentation
+    fn format_value(&self, value: &Value, indent: &str) -> Result<String, EditError> {
+        let indent_unit = self.indent_unit();
+        let mut bytes = Vec::new();
+        let mut serializer = Serializer::with_formatter(
+            &mut bytes,
+            PrettyFormatter::with_indent(indent_unit.as_bytes()),
+        );
+
+        value
+            .serialize(&mut serializer)
+            .into_report()
+            .change_context(EditError::InvalidValue)?;
+
+        let formatted = String::from_utf8(bytes)
+            .into_report()
+            .change_context(EditError::InvalidValue)?;
+
+        Ok(formatted.replace('\n', &format!("\n{indent}")))
+    }
+
+    /// Guess the indent unit of the document from the first indented line, defaulting to two spaces
+    fn indent_unit(&self) -> String {
+        self.source
+            .lines()
+            .map(|line| &line[..line.len() - line.trim_start().len()])
+            .find(|indent| !indent.is_empty())
+            .unwrap_or("  ")
+            .to_string()
+    }
+}
+
+/// Get the indentation of the line containing `index`
+fn line_indent(source: &str, index: usize) -> &str {
+    let line_start = source[..index].rfind('\n').map_or(0, |newline| newline + 1);
+    let line = &source[line_start..];
+
+    &line[..line.len() - line.trim_start().len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"{
+    "packages": {
+        "github:nyoom-engineering/nyoom.nvim": {
+            "optional": true,
+            "version": "branch:main"
+        },
+        "git:github.com:catppuccin/nvim": { "rename": "catppuccin" }
+    }
+}
+"#;
+
+    #[test]
+    fn test_add_and_remove_package() {
+        let mut document = ConfigDocument::new(CONFIG.to_string()).unwrap();
+
+        document
+            .add_package(
+                "github:nvim-lua/plenary.nvim",
+                &ConfigPackage {
+                    version: Some("tag:v0.1.3".to_string()),
+                    ..ConfigPackage::default()
+                },
+            )
+            .unwrap();
+
+        assert!(document.as_str().contains(
+            "        \"github:nvim-lua/plenary.nvim\": {\n            \"version\": \"tag:v0.1.3\"\n        }\n    }"
+        ));
+        assert!(document
+            .add_package("github:nvim-lua/plenary.nvim", &ConfigPackage::default())
+            .is_err());
+
+        document
+            .remove_package("github:nvim-lua/plenary.nvim")
+            .unwrap();
+        assert_eq!(document.as_str(), CONFIG);
+    }
+
+    #[test]
+    fn test_set_fields() {
+        let mut document = ConfigDocument::new(CONFIG.to_string()).unwrap();
+
+        document
+            .set_version("github:nyoom-engineering/nyoom.nvim", Some("tag:v1.0.0"))
+            .unwrap();
+        document
+            .set_optional("github:nyoom-engineering/nyoom.nvim", false)
+            .unwrap();
+        document
+            .set_optional("git:github.com:catppuccin/nvim", true)
+            .unwrap();
+
+        assert_eq!(
+            document.as_str(),
+            r#"{
+    "packages": {
+        "github:nyoom-engineering/nyoom.nvim": {
+            "version": "tag:v1.0.0"
+        },
+        "git:github.com:catppuccin/nvim": { "rename": "catppuccin", "optional": true }
+    }
+}
+"#
+        );
+
+        assert!(document
+            .set_version("github:nyoom-engineering/nyoom.nvim", Some("v1.0.0"))
+            .is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! Stable config hashing
+//!
+//! Generations are looked up by the hash of the config they were created from, so the hash has to stay the same
+//! across alpacka and Rust releases. It is the SHA-256 of a canonical serialization of the config: the compact JSON of
+//! `{"packages": ..., "overrides": ..., "conditions": [...]}`, where map keys are sorted, struct fields are in declaration order, and
+//! unset fields of packages and an empty `overrides` are left out. Leaving out unset fields means that adding a new
+//! optional field to the config format does not change the hash of configs which do not use it. `conditions` is always
+//! written, as an empty list if no package has a condition.
+
+use super::Config;
+use crate::{
+    condition::ConditionOutcome,
+    manifest::ConfigHash,
+    package::{Config as ConfigPackage, Override},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    hash::{Hash, Hasher},
+};
+
+/// The part of a config which is hashed
+///
+/// The schema is editor metadata, includes are resolved before hashing, and groups and profiles only select packages,
+/// so only the packages and overrides are hashed. Patches are hashed by their contents instead of their paths, and the
+/// condition outcomes are hashed as they change what is loaded.
+#[derive(Serialize)]
+struct Canonical<'a> {
+    packages: &'a BTreeMap<String, ConfigPackage>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    overrides: &'a BTreeMap<String, Override>,
+    conditions: &'a [ConditionOutcome],
+}
+
+/// A package as it was hashed before stable hashing, with the fields it had at the time
+#[derive(Hash)]
+struct LegacyPackage<'a> {
+    optional: &'a Option<bool>,
+    version: &'a Option<String>,
+    rename: &'a Option<String>,
+    build: &'a Option<String>,
+    dependencies: BTreeMap<&'a String, Self>,
+}
+
+/// Convert packages to the form they were hashed in before stable hashing
+fn legacy_packages(
+    packages: &BTreeMap<String, ConfigPackage>,
+) -> BTreeMap<&String, LegacyPackage<'_>> {
+    packages
+        .iter()
+        .map(|(name, package)| {
+            (
+                name,
+                LegacyPackage {
+                    optional: &package.optional,
+                    version: &package.version,
+                    rename: &package.rename,
+                    build: &package.build,
+                    dependencies: legacy_packages(&package.dependencies),
+                },
+            )
+        })
+        .collect()
+}
+
+impl Config {
+    /// Compute the stable hash of this config and the outcomes of its conditions
+    ///
+    /// This should be called on a config returned by [`Config::load`], after selecting a profile
+    ///
+    /// # Panics
+    /// Cannot panic, as configs only contain values which can be serialized to JSON
+    #[must_use]
+    pub fn stable_hash(&self, conditions: &[ConditionOutcome]) -> ConfigHash {
+        let canonical = serde_json::to_vec(&Canonical {
+            packages: &self.packages_with_patch_digests(),
+            overrides: &self.overrides,
+            conditions,
+        })
+        .expect("configs can always be serialized to JSON");
+
+        ConfigHash::Sha256(Sha256::digest(canonical).into())
+    }
+
+    /// Compute the hash generations were stored under before stable hashing
+    ///
+    /// This is the [`DefaultHasher`] hash of the packages, and is only used to migrate old generations files.
+    /// It only matches the hash of an older alpacka if it was built with a Rust release whose [`DefaultHasher`] behaves the same.
+    #[must_use]
+    pub fn legacy_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        legacy_packages(&self.packages).hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_stable_hash() {
+        let mut config = Config::from_json(
+            r#"{ "packages": { "github:nvim-lua/plenary.nvim": { "version": "tag:v0.1.3" } } }"#,
+            Path::new("packages.json"),
+        )
+        .unwrap();
+
+        // changing this hash orphans every generation, so it needs a migration
+        assert_eq!(
+            config.stable_hash(&[]).to_string(),
+            "sha256:b00a72bdb8279c929d5c7ddcf3d6f44e8fffab81925621f5304cbbfaf672f4d2"
+        );
+
+        let hash = config.stable_hash(&[]);
+        config.schema = Some("schema.json".to_string());
+        assert_eq!(config.stable_hash(&[]), hash);
+    }
+
+    #[test]
+    fn test_stable_hash_without_conditions() {
+        let config = Config::from_json(
+            r#"{ "packages": { "github:nvim-lua/plenary.nvim": { "version": "tag:v0.1.3" } } }"#,
+            Path::new("packages.json"),
+        )
+        .unwrap();
+
+        // the canonical form still has an empty `conditions` list
+        assert_eq!(
+            config.stable_hash(&[]),
+            ConfigHash::Sha256(
+                Sha256::digest(
+                    r#"{"packages":{"github:nvim-lua/plenary.nvim":{"version":"tag:v0.1.3"}},"conditions":[]}"#
+                )
+                .into()
+            )
+        );
+    }
+}
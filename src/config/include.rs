@@ -0,0 +1,269 @@
+//! Config includes and local overlays
+//!
+//! A config can include other config files, which are merged in before the config itself.
+//! A local overlay (`packages.local.json` next to `packages.json`) is merged on top of everything else,
+//! which allows per-machine additions without changing a shared config.
+
+use super::{expand_env, read_source, Config, LoadConfigError};
+use crate::{manifest::ConfigSource, package::merge_packages};
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+impl Config {
+    /// Load a config file, the files it includes, and its local overlay.
+    ///
+    /// The result has no includes left, has its variables expanded with [`Config::interpolate`] and its patches hashed
+    /// with [`Config::hash_patches`], and is what should be hashed and resolved.
+    /// See [`local_overlay_path`] for where the overlay is looked up.
+    ///
+    /// # Errors
+    /// This function will return an error if any of the files cannot be loaded, or if the includes form a cycle
+    #[tracing::instrument]
+    pub fn load(path: &Path) -> Result<Self, LoadConfigError> {
+        Self::load_with_sources(path).map(|(config, _)| config)
+    }
+
+    /// Load a config like [`Config::load`], and also return the contents of every file it was loaded from
+    ///
+    /// The config file comes first, followed by the files it includes in the order they are loaded, and the local
+    /// overlay and its includes last. A file which is included more than once is only returned once. Writing the
+    /// sources back to their paths restores the config.
+    ///
+    /// # Errors
+    /// This function will return an error if any of the files cannot be loaded, or if the includes form a cycle
+    #[tracing::instrument]
+    pub fn load_with_sources(path: &Path) -> Result<(Self, Vec<ConfigSource>), LoadConfigError> {
+        let root = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut sources = Sources {
+            root,
+            sources: Vec::new(),
+        };
+
+        let mut config = load_with_includes(path, &mut Vec::new(), &mut sources)?;
+
+        if let Some(overlay_path) = local_overlay_path(path).filter(|path| path.exists()) {
+            let overlay = load_with_includes(&overlay_path, &mut Vec::new(), &mut sources)
+                .attach_printable_lazy(|| {
+                    format!("Local overlay path: {}", overlay_path.display())
+                })?;
+
+            config.merge(overlay);
+        }
+
+        // a file without includes keeps its `disabled` packages, so that they can disable packages of the config it is
+        // merged onto. Those left over did not disable anything
+        config
+            .packages
+            .retain(|_, package| !package.disabled.unwrap_or(false));
+
+        config.interpolate()?;
+        config.hash_patches()?;
+
+        Ok((config, sources.sources))
+    }
+
+    /// Merge another config on top of this one.
+    ///
+    /// Packages are merged with [`merge_packages`], so the other config can add packages, override their fields, or
+    /// remove them by marking them as `disabled`. Groups, profiles and overrides from the other config replace the ones
+    /// with the same name.
+    pub fn merge(&mut self, other: Self) {
+        merge_packages(&mut self.packages, other.packages);
+        self.groups.extend(other.groups);
+        self.profiles.extend(other.profiles);
+        self.overrides.extend(other.overrides);
+    }
+}
+
+/// Get the path of the local overlay for a config file
+///
+/// The overlay has the same name as the config with `.local` added before the extension,
+/// e.g. `packages.local.json` for `packages.json`
+#[must_use]
+pub fn local_overlay_path(path: &Path) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_str()?;
+
+    Some(path.extension().and_then(OsStr::to_str).map_or_else(
+        || path.with_file_name(format!("{stem}.local")),
+        |extension| path.with_file_name(format!("{stem}.local.{extension}")),
+    ))
+}
+
+/// The files a config is loaded from, with their paths relative to the directory of the config file
+struct Sources<'a> {
+    root: &'a Path,
+    sources: Vec<ConfigSource>,
+}
+
+impl Sources<'_> {
+    /// Record the contents of a file, unless it was already recorded
+    fn push(&mut self, path: &Path, contents: String) {
+        let path = path.strip_prefix(self.root).map_or_else(
+            |_| path.display().to_string(),
+            |path| path.display().to_string(),
+        );
+
+        if !self.sources.iter().any(|source| source.path == path) {
+            self.sources.push(ConfigSource { path, contents });
+        }
+    }
+}
+
+/// Load a config file and recursively merge in the files it includes
+///
+/// `stack` contains the files which are currently being loaded, and is used to detect include cycles. The contents of
+/// every file are added to `sources`
+fn load_with_includes(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    sources: &mut Sources,
+) -> Result<Config, LoadConfigError> {
+    let canonical_path = path
+        .canonicalize()
+        .into_report()
+        .attach_printable_lazy(|| format!("Config file path: {}", path.display()))
+        .change_context(LoadConfigError::Read)?;
+
+    if stack.contains(&canonical_path) {
+        return Err(
+            Report::new(LoadConfigError::Include).attach_printable(format!(
+                "Include cycle detected: {} is included by itself",
+                path.display()
+            )),
+        );
+    }
+
+    stack.push(canonical_path);
+
+    let parent = path.parent().unwrap_or_else(|| Path::new(""));
+    let source = read_source(path)?;
+    let mut file = Config::from_source(&source, path)?;
+    file.resolve_patch_paths(parent)?;
+    sources.push(path, source);
+
+    if file.include.is_empty() {
+        stack.pop();
+        return Ok(file);
+    }
+
+    let mut config = Config::default();
+
+    for include in std::mem::take(&mut file.include) {
+        let include = expand_env(&include.to_string_lossy())
+            .map_err(|error| Report::new(LoadConfigError::Interpolate).attach_printable(error))
+            .attach_printable_lazy(|| format!("Included from: {}", path.display()))?;
+
+        let included = load_with_includes(&parent.join(include), stack, sources)
+            .attach_printable_lazy(|| format!("Included from: {}", path.display()))
+            .change_context(LoadConfigError::Include)?;
+
+        config.merge(included);
+    }
+
+    config.merge(file);
+    stack.pop();
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_with_include_and_overlay() {
+        let dir = tempfile::tempdir().unwrap();
+
+        std::fs::write(
+            dir.path().join("base.json"),
+            r#"{ "packages": {
+                "github:nvim-lua/plenary.nvim": {},
+                "github:nvim-telescope/telescope.nvim": { "version": "branch:master" }
+            } }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("packages.json"),
+            r#"{ "include": ["base.json"], "packages": {
+                "github:nyoom-engineering/oxocarbon.nvim": {}
+            } }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("packages.local.json"),
+            r#"{ "packages": {
+                "github:nvim-lua/plenary.nvim": { "disabled": true },
+                "github:nvim-telescope/telescope.nvim": { "version": "tag:0.1.0" }
+            } }"#,
+        )
+        .unwrap();
+
+        let config = Config::load(&dir.path().join("packages.json")).unwrap();
+
+        assert!(config.include.is_empty());
+        assert_eq!(
+            config.packages.keys().collect::<Vec<_>>(),
+            [
+                "github:nvim-telescope/telescope.nvim",
+                "github:nyoom-engineering/oxocarbon.nvim"
+            ]
+        );
+        assert_eq!(
+            config.packages["github:nvim-telescope/telescope.nvim"]
+                .version
+                .as_deref(),
+            Some("tag:0.1.0")
+        );
+    }
+
+    #[test]
+    fn test_load_with_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = r#"{ "packages": { "github:nvim-lua/plenary.nvim": {} } }"#;
+        let config = r#"{ "include": ["shared/base.json", "shared/base.json"], "packages": {} }"#;
+        let overlay = r#"{ "packages": { "github:nvim-lua/plenary.nvim": { "disabled": true } } }"#;
+
+        std::fs::create_dir(dir.path().join("shared")).unwrap();
+        std::fs::write(dir.path().join("shared/base.json"), base).unwrap();
+        std::fs::write(dir.path().join("packages.json"), config).unwrap();
+        std::fs::write(dir.path().join("packages.local.json"), overlay).unwrap();
+
+        let (loaded, sources) =
+            Config::load_with_sources(&dir.path().join("packages.json")).unwrap();
+
+        assert!(loaded.packages.is_empty());
+        assert_eq!(
+            sources,
+            [
+                ConfigSource {
+                    path: "packages.json".to_string(),
+                    contents: config.to_string(),
+                },
+                ConfigSource {
+                    path: "shared/base.json".to_string(),
+                    contents: base.to_string(),
+                },
+                ConfigSource {
+                    path: "packages.local.json".to_string(),
+                    contents: overlay.to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+
+        std::fs::write(
+            dir.path().join("packages.json"),
+            r#"{ "include": ["packages.json"], "packages": {} }"#,
+        )
+        .unwrap();
+
+        assert!(Config::load(&dir.path().join("packages.json")).is_err());
+    }
+}
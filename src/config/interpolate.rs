@@ -0,0 +1,228 @@
+//! Environment variable and home directory expansion in config values
+//!
+//! `${NAME}` is replaced with the value of an environment variable, and `${NAME:-default}` falls back to the expanded
+//! `default` when the variable is not set. `~` at the start of a word is replaced with the home directory, and `$$` is a literal `$`.
+
+use super::{Config, LoadConfigError};
+use crate::package::Config as ConfigPackage;
+use error_stack::{Report, Result};
+use std::collections::BTreeMap;
+
+/// Expand variables and `~` in a value, using `lookup` to get the value of a variable
+///
+/// # Errors
+/// Returns a description of the problem if a variable is not defined and has no default, or if a `${` is not closed
+pub fn expand(
+    value: &str,
+    lookup: &impl Fn(&str) -> Option<String>,
+) -> std::result::Result<String, String> {
+    let mut output = String::with_capacity(value.len());
+    let mut chars = value.char_indices().peekable();
+    let mut at_word_start = true;
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '~' if at_word_start
+                && chars
+                    .peek()
+                    .is_none_or(|(_, next)| *next == '/' || next.is_whitespace()) =>
+            {
+                let home = lookup("HOME").ok_or_else(|| {
+                    format!("`HOME` is not set, so `~` cannot be expanded in `{value}`")
+                })?;
+
+                output.push_str(&home);
+            }
+            '$' => match chars.peek() {
+                Some((_, '$')) => {
+                    chars.next();
+                    output.push('$');
+                }
+                Some((_, '{')) => {
+                    let end = find_closing_brace(value, index)
+                        .ok_or_else(|| format!("Unclosed `${{` in `{value}`"))?;
+
+                    let expression = &value[index + 2..end];
+                    let expanded = match expression.split_once(":-") {
+                        Some((name, default)) => match lookup(name) {
+                            Some(variable) => variable,
+                            None => expand(default, lookup)?,
+                        },
+                        None => lookup(expression).ok_or_else(|| {
+                            format!("Variable `{expression}` is not set and has no default in `{value}`")
+                        })?,
+                    };
+
+                    output.push_str(&expanded);
+
+                    while chars
+                        .next_if(|(next_index, _)| *next_index <= end)
+                        .is_some()
+                    {}
+                }
+                _ => output.push('$'),
+            },
+            c => output.push(c),
+        }
+
+        at_word_start = c.is_whitespace();
+    }
+
+    Ok(output)
+}
+
+/// Find the `}` closing the `${` at `start`, skipping over nested `${...}` and `$$`
+fn find_closing_brace(value: &str, start: usize) -> Option<usize> {
+    let bytes = value.as_bytes();
+    let mut depth = 0_usize;
+    let mut index = start + 2;
+
+    while index < bytes.len() {
+        match bytes[index] {
+            b'$' if bytes.get(index + 1) == Some(&b'$') => index += 1,
+            b'$' if bytes.get(index + 1) == Some(&b'{') => {
+                depth += 1;
+                index += 1;
+            }
+            b'}' if depth == 0 => return Some(index),
+            b'}' => depth -= 1,
+            _ => {}
+        }
+
+        index += 1;
+    }
+
+    None
+}
+
+/// Expand variables in a value using the environment
+///
+/// # Errors
+/// See [`expand`]
+pub fn expand_env(value: &str) -> std::result::Result<String, String> {
+    expand(value, &|name| std::env::var(name).ok())
+}
+
+/// Expand variables in the source of an override, and `~` after its `path:` prefix
+///
+/// # Errors
+/// See [`expand`]
+fn expand_source(
+    source: &str,
+    lookup: &impl Fn(&str) -> Option<String>,
+) -> std::result::Result<String, String> {
+    source.strip_prefix("path:").map_or_else(
+        || expand(source, lookup),
+        |path| expand(path, lookup).map(|path| format!("path:{path}")),
+    )
+}
+
+/// Expand the `build` and `rename` fields of a list of packages and their dependencies
+fn interpolate_packages(
+    packages: &mut BTreeMap<String, ConfigPackage>,
+) -> Result<(), LoadConfigError> {
+    for (name, package) in packages {
+        for (field, value) in [
+            ("build", &mut package.build),
+            ("rename", &mut package.rename),
+        ] {
+            if let Some(value) = value {
+                *value = expand_env(value).map_err(|error| {
+                    Report::new(LoadConfigError::Interpolate)
+                        .attach_printable(error)
+                        .attach_printable(format!("Package: {name}, field: {field}"))
+                })?;
+            }
+        }
+
+        interpolate_packages(&mut package.dependencies)?;
+    }
+
+    Ok(())
+}
+
+impl Config {
+    /// Expand environment variables and `~` in the fields of every package and override which can contain paths or commands
+    ///
+    /// This is done by [`Config::load`], so the hash of a config is computed over the expanded values.
+    ///
+    /// # Errors
+    /// This function will return an error if a variable is not set and has no default
+    pub fn interpolate(&mut self) -> Result<(), LoadConfigError> {
+        interpolate_packages(&mut self.packages)?;
+
+        for (name, package_override) in &mut self.overrides {
+            if let Some(source) = &mut package_override.source {
+                *source =
+                    expand_source(source, &|name| std::env::var(name).ok()).map_err(|error| {
+                        Report::new(LoadConfigError::Interpolate)
+                            .attach_printable(error)
+                            .attach_printable(format!("Override: {name}, field: source"))
+                    })?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/alpacka".to_string()),
+            "XDG_DATA_HOME" => Some("/data".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(
+            expand("make PREFIX=${XDG_DATA_HOME}/nvim", &lookup).unwrap(),
+            "make PREFIX=/data/nvim"
+        );
+        assert_eq!(
+            expand("cp ~/foo ~", &lookup).unwrap(),
+            "cp /home/alpacka/foo /home/alpacka"
+        );
+        assert_eq!(
+            expand("${CARGO_HOME:-~/.cargo}", &lookup).unwrap(),
+            "/home/alpacka/.cargo"
+        );
+        assert_eq!(expand("a~b $${HOME}", &lookup).unwrap(), "a~b ${HOME}");
+        assert_eq!(expand("${A:-${HOME}}", &lookup).unwrap(), "/home/alpacka");
+        assert_eq!(
+            expand("${XDG_DATA_HOME:-${HOME}}/nvim", &lookup).unwrap(),
+            "/data/nvim"
+        );
+        // the default is only expanded if the variable is not set
+        assert_eq!(expand("${HOME:-${B}}", &lookup).unwrap(), "/home/alpacka");
+    }
+
+    #[test]
+    fn test_expand_source() {
+        assert_eq!(
+            expand_source("path:~/src/plenary.nvim", &lookup).unwrap(),
+            "path:/home/alpacka/src/plenary.nvim"
+        );
+        assert_eq!(
+            expand_source("path:${XDG_DATA_HOME}/plenary.nvim", &lookup).unwrap(),
+            "path:/data/plenary.nvim"
+        );
+        assert_eq!(
+            expand_source("github:~user/plenary.nvim", &lookup).unwrap(),
+            "github:~user/plenary.nvim"
+        );
+    }
+
+    #[test]
+    fn test_expand_undefined() {
+        assert!(expand("${UNDEFINED}", &lookup).is_err());
+        assert!(expand("${HOME", &lookup).is_err());
+        assert!(expand("${A:-${B}}", &lookup).is_err());
+        assert!(expand("${A:-${HOME}", &lookup).is_err());
+    }
+}
@@ -0,0 +1,210 @@
+//! Evaluation of `packages.lua` config files
+//!
+//! The config file is run inside of an embedded Lua interpreter and must return a table with the same shape as the
+//! JSON config format. The interpreter is sandboxed: there is no `io`, `package`/`require`, `dofile`, `loadfile`, `load`
+//! or `print`, and the `os` table only has functions that cannot touch the filesystem or spawn processes. Configs which
+//! run for too many instructions or use too much memory are stopped, so a mistake such as `while true do end` cannot
+//! hang `alpacka install`.
+
+use super::{validate::attach_suggestion, Config, LoadConfigError, Location};
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use mlua::{HookTriggers, Lua, LuaOptions, LuaSerdeExt, StdLib, Table, Value};
+use std::path::PathBuf;
+
+/// Globals which are removed from the interpreter before the config is evaluated
+const BLOCKED_GLOBALS: [&str; 4] = ["dofile", "loadfile", "load", "print"];
+
+/// The number of Lua instructions a config can run before it is stopped
+const INSTRUCTION_LIMIT: u32 = 100_000_000;
+
+/// The memory a config can use, in bytes
+const MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Functions which are removed from the `os` table before the config is evaluated
+const BLOCKED_OS_FUNCTIONS: [&str; 6] = [
+    "execute",
+    "exit",
+    "remove",
+    "rename",
+    "tmpname",
+    "setlocale",
+];
+
+/// Create a sandboxed Lua interpreter
+fn create_sandbox() -> mlua::Result<Lua> {
+    let lua = Lua::new_with(
+        StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8 | StdLib::OS,
+        LuaOptions::default(),
+    )?;
+
+    {
+        let globals = lua.globals();
+
+        for global in BLOCKED_GLOBALS {
+            globals.set(global, Value::Nil)?;
+        }
+
+        let os: Table = globals.get("os")?;
+
+        for function in BLOCKED_OS_FUNCTIONS {
+            os.set(function, Value::Nil)?;
+        }
+    }
+
+    lua.set_memory_limit(MEMORY_LIMIT)?;
+    lua.set_hook(
+        HookTriggers::new().every_nth_instruction(INSTRUCTION_LIMIT),
+        |_, _| {
+            Err(mlua::Error::runtime(format!(
+                "config ran for more than {INSTRUCTION_LIMIT} instructions"
+            )))
+        },
+    );
+
+    Ok(lua)
+}
+
+/// Find the line of a Lua error, from a message such as `packages.lua:3: unexpected symbol near '}'`
+fn error_line(message: &str, chunk_name: &str) -> Option<usize> {
+    let (_, rest) = message.split_once(&format!("{chunk_name}:"))?;
+
+    rest.split(':').next()?.parse().ok()
+}
+
+/// Find the line and column of the first value quoted with backticks in an error message, such as an unknown field
+///
+/// The returned table does not remember where its values were defined, so they are searched for in the source instead
+fn find_quoted(source: &str, message: &str) -> Option<(usize, usize)> {
+    let (_, rest) = message.split_once('`')?;
+    let (value, _) = rest
+        .split_once('`')
+        .filter(|(value, _)| !value.is_empty())?;
+
+    source.lines().enumerate().find_map(|(index, line)| {
+        line.find(value)
+            .map(|column| (index + 1, line[..column].chars().count() + 1))
+    })
+}
+
+impl Config {
+    /// Evaluate a Lua config file and convert the returned table into a config.
+    ///
+    /// `chunk_name` is used in error messages, and should usually be the path of the config file.
+    /// Syntax and runtime errors will contain the line the error occurred on.
+    ///
+    /// # Errors
+    /// This function will return an error if the Lua code fails to run, or if the returned value is not a valid config.
+    /// The error will have a [`Location`] attached. Lua does not report columns, so errors from running the code point
+    /// at the start of the line, and errors which cannot be traced back to the source point at the start of the file.
+    #[tracing::instrument(skip(source))]
+    pub fn from_lua(source: &str, chunk_name: &str) -> Result<Self, LoadConfigError> {
+        let lua = create_sandbox()
+            .into_report()
+            .attach_printable_lazy(|| "Failed to create Lua sandbox")
+            .change_context(LoadConfigError::Parse)?;
+
+        let location = |(line, column)| Location {
+            path: PathBuf::from(chunk_name),
+            line,
+            column,
+        };
+
+        let value = lua
+            .load(source)
+            .set_name(format!("@{chunk_name}"))
+            .eval::<Value>()
+            .map_err(|error| {
+                let line = error_line(&error.to_string(), chunk_name).unwrap_or(1);
+
+                Report::new(error)
+                    .attach_printable(format!("Failed to evaluate Lua config: {chunk_name}"))
+                    .change_context(LoadConfigError::Parse)
+                    .attach_printable(location((line, 1)))
+            })?;
+
+        lua.from_value(value).map_err(|error| {
+            let message = error.to_string();
+
+            let report = Report::new(error)
+                .attach_printable(format!(
+                    "Lua config did not return a valid config table: {chunk_name}"
+                ))
+                .change_context(LoadConfigError::Parse)
+                .attach_printable(location(find_quoted(source, &message).unwrap_or((1, 1))));
+
+            attach_suggestion(report, &message)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_lua() {
+        let config = Config::from_lua(
+            r#"
+            local deps = { ["github:nyoom-engineering/oxocarbon.nvim"] = {} }
+
+            return {
+                packages = {
+                    ["github:nyoom-engineering/nyoom.nvim"] = {
+                        optional = true,
+                        version = "branch:" .. string.lower("MAIN"),
+                        dependencies = deps,
+                    },
+                },
+            }
+            "#,
+            "packages.lua",
+        )
+        .unwrap();
+
+        let package = &config.packages["github:nyoom-engineering/nyoom.nvim"];
+
+        assert_eq!(package.optional, Some(true));
+        assert_eq!(package.version.as_deref(), Some("branch:main"));
+        assert!(package
+            .dependencies
+            .contains_key("github:nyoom-engineering/oxocarbon.nvim"));
+    }
+
+    #[test]
+    fn test_from_lua_is_sandboxed() {
+        assert!(Config::from_lua("return io.open('/etc/passwd')", "packages.lua").is_err());
+        assert!(Config::from_lua("os.execute('true') return {}", "packages.lua").is_err());
+        assert!(Config::from_lua("return load('return {}')()", "packages.lua").is_err());
+        assert!(Config::from_lua("print('hello') return {}", "packages.lua").is_err());
+    }
+
+    #[test]
+    fn test_from_lua_is_stopped() {
+        assert!(Config::from_lua("while true do end", "packages.lua").is_err());
+        assert!(Config::from_lua("return string.rep('a', 1 << 30)", "packages.lua").is_err());
+    }
+
+    #[test]
+    fn test_from_lua_error_has_line_number() {
+        let error =
+            Config::from_lua("return {\n  packages = nil +\n}", "packages.lua").unwrap_err();
+
+        assert!(format!("{error:?}").contains("packages.lua:3"));
+
+        let location = error.downcast_ref::<Location>().unwrap();
+        assert_eq!((location.line, location.column), (3, 1));
+    }
+
+    #[test]
+    fn test_from_lua_rejects_unknown_fields() {
+        let error = Config::from_lua(
+            "return {\n  packages = {\n    ['github:a/b'] = { opt = true },\n  },\n}",
+            "packages.lua",
+        )
+        .unwrap_err();
+
+        let location = error.downcast_ref::<Location>().unwrap();
+        assert_eq!(location.path, PathBuf::from("packages.lua"));
+        assert_eq!((location.line, location.column), (3, 24));
+    }
+}
@@ -0,0 +1,290 @@
+//! A module which contains the alpacka config format, and functions to load it
+
+mod edit;
+mod hash;
+mod include;
+mod interpolate;
+mod lua;
+mod patch;
+mod profile;
+mod schema;
+mod validate;
+
+pub use edit::{ConfigDocument, EditError};
+pub use include::local_overlay_path;
+pub use interpolate::{expand, expand_env};
+pub use profile::{profile_data_path, Profile, ProfileError};
+pub use schema::schema;
+pub(crate) use validate::suggest;
+pub use validate::{Location, Suggestion};
+
+use crate::{
+    condition::{ConditionContext, ConditionOutcome},
+    graph::{DependencyGraph, GraphError},
+    package::{Config as ConfigPackage, Override, Package, WithSmith},
+    smith::enums::Loaders,
+};
+use error_stack::{Context, IntoReport, Result, ResultExt};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(deny_unknown_fields)]
+/// The alpacka config format
+pub struct Config {
+    /// The JSON schema used by editors to validate the config. This is ignored by alpacka
+    #[serde(rename = "$schema", default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    /// Other config files to merge into this one, relative to this file. Later files take precedence, and this file
+    /// takes precedence over all of them. Environment variables and `~` are expanded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<PathBuf>,
+    /// All the packages
+    pub packages: BTreeMap<String, ConfigPackage>,
+    /// Named groups of packages, which can be enabled or disabled by profiles
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, Vec<String>>,
+    /// Named profiles, each of which selects a subset of the packages
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Overrides of the source or version of packages, keyed by package name. These apply wherever the package
+    /// appears, including as a dependency of other packages
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub overrides: BTreeMap<String, Override>,
+    /// The hashes of the contents of every patch, keyed by path. This is filled in by [`Config::load`]
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub patch_digests: BTreeMap<String, String>,
+}
+
+#[derive(Debug)]
+/// An error that can occur when creating a list of packages
+pub enum CreatePackageListError {
+    /// No loader found for a package
+    NoLoaderFound(String),
+    /// The packages are requested with conflicting fields, or their dependencies form a cycle
+    InvalidGraph,
+}
+
+impl Display for CreatePackageListError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoLoaderFound(name) => {
+                write!(f, "No loader found for package {name}")
+            }
+            Self::InvalidGraph => f.write_str("Failed to build the dependency graph"),
+        }
+    }
+}
+
+impl Context for CreatePackageListError {}
+
+#[derive(Debug)]
+/// An error that can occur when loading a config file
+pub enum LoadConfigError {
+    /// The config file could not be read
+    Read,
+    /// The config file could not be parsed
+    Parse,
+    /// An included config file could not be loaded
+    Include,
+    /// A patch file could not be read
+    Patch,
+    /// A variable in the config could not be expanded
+    Interpolate,
+}
+
+impl Display for LoadConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Read => "Failed to read config file",
+            Self::Parse => "Failed to parse config file",
+            Self::Include => "Failed to load included config file",
+            Self::Patch => "Failed to read patch file",
+            Self::Interpolate => "Failed to expand variables in config file",
+        })
+    }
+}
+
+impl Context for LoadConfigError {}
+
+impl Config {
+    /// Load a config file from a path.
+    ///
+    /// Files ending in `.lua` are evaluated with [`Config::from_lua`], every other file is parsed with [`Config::from_json`].
+    ///
+    /// # Errors
+    /// This function will return an error if the file cannot be read, or if it is not a valid config
+    #[tracing::instrument]
+    pub fn from_path(path: &Path) -> Result<Self, LoadConfigError> {
+        let source = read_source(path)?;
+
+        Self::from_source(&source, path)
+    }
+
+    /// Parse the contents of a config file, as Lua if `path` has the `lua` extension, else as JSON
+    ///
+    /// # Errors
+    /// This function will return an error if the config is invalid
+    pub fn from_source(source: &str, path: &Path) -> Result<Self, LoadConfigError> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("lua") => Self::from_lua(source, &path.display().to_string()),
+            _ => Self::from_json(source, path),
+        }
+    }
+
+    /// Evaluate the conditions of every package and dependency which has one
+    ///
+    /// The outcomes are stored in the manifest, and are hashed alongside the config by [`Config::stable_hash`], as they change which packages are loaded
+    #[must_use]
+    pub fn evaluate_conditions(&self, context: &ConditionContext) -> Vec<ConditionOutcome> {
+        fn evaluate(
+            packages: &BTreeMap<String, ConfigPackage>,
+            context: &ConditionContext,
+            outcomes: &mut Vec<ConditionOutcome>,
+        ) {
+            for (name, package) in packages {
+                if let Some(condition) = &package.when {
+                    outcomes.extend(condition.evaluate(name, context));
+                }
+
+                evaluate(&package.dependencies, context, outcomes);
+            }
+        }
+
+        let mut outcomes = Vec::new();
+        evaluate(&self.packages, context, &mut outcomes);
+
+        outcomes
+    }
+
+    /// Create a list of the top level packages with their corresponding smith
+    ///
+    /// Packages whose condition does not pass are skipped. The smiths are picked by [`DependencyGraph::new`], so a
+    /// config with conflicting or cyclic dependencies is rejected too.
+    ///
+    /// # Errors
+    /// This function will return an error if no loader can be found for a package, or if the dependency graph of the
+    /// config is not valid
+    #[deprecated(
+        note = "use `DependencyGraph::new`, which merges packages requested several times into one"
+    )]
+    #[tracing::instrument(skip(self))]
+    pub fn create_package_list(
+        &self,
+        smiths: &[Loaders],
+        context: &ConditionContext,
+    ) -> Result<Vec<WithSmith<'_>>, CreatePackageListError> {
+        let graph = DependencyGraph::new(self, smiths, context).map_err(|report| {
+            let error = match report.current_context() {
+                GraphError::NoLoaderFound(name) => {
+                    CreatePackageListError::NoLoaderFound(name.clone())
+                }
+                GraphError::Conflict | GraphError::Cycle => CreatePackageListError::InvalidGraph,
+            };
+
+            report.change_context(error)
+        })?;
+
+        Ok(self
+            .packages
+            .iter()
+            .filter_map(|(name, config_package)| {
+                let node = graph.get(name)?;
+
+                Some(WithSmith {
+                    smith: node.smith.clone(),
+                    package: Package {
+                        name,
+                        source: self
+                            .overrides
+                            .get(name)
+                            .and_then(|package_override| package_override.source.as_deref())
+                            .unwrap_or(name),
+                        config_package,
+                    },
+                })
+            })
+            .collect())
+    }
+
+    /// Serialize this config as pretty printed JSON with sorted keys, which can be written back to a config file
+    ///
+    /// A config returned by [`Config::load`] has its includes and local overlay merged and its variables expanded, so
+    /// its snapshot is a single JSON file which loads to the same config
+    ///
+    /// # Panics
+    /// Cannot panic, as configs only contain values which can be serialized to JSON
+    #[must_use]
+    pub fn snapshot(&self) -> String {
+        serde_json::to_string_pretty(self).expect("configs can always be serialized to JSON") + "\n"
+    }
+}
+
+/// Read the contents of a config file
+fn read_source(path: &Path) -> Result<String, LoadConfigError> {
+    std::fs::read_to_string(path)
+        .into_report()
+        .attach_printable_lazy(|| format!("Config file path: {}", path.display()))
+        .change_context(LoadConfigError::Read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_loads_to_the_same_config() {
+        let config = Config::from_json(
+            r#"{
+                "packages": {
+                    "github:nvim-telescope/telescope.nvim": {
+                        "version": "branch:master",
+                        "dependencies": { "github:nvim-lua/plenary.nvim": {} }
+                    }
+                },
+                "groups": { "ui": ["github:nvim-telescope/telescope.nvim"] }
+            }"#,
+            Path::new("packages.json"),
+        )
+        .unwrap();
+
+        let snapshot = Config::from_json(&config.snapshot(), Path::new("packages.json")).unwrap();
+
+        assert_eq!(snapshot, config);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_create_package_list() {
+        let config = Config::from_json(
+            r#"{
+                "packages": {
+                    "github:nvim-lua/plenary.nvim": {},
+                    "github:nvim-telescope/telescope.nvim": { "when": { "os": ["windows"] } }
+                },
+                "overrides": { "github:nvim-lua/plenary.nvim": { "source": "github:fork/plenary.nvim" } }
+            }"#,
+            Path::new("packages.json"),
+        )
+        .unwrap();
+        let context = ConditionContext {
+            os: "linux".to_string(),
+            arch: "x86_64".to_string(),
+            hostname: None,
+            neovim_version: None,
+        };
+        let smiths = [Loaders::Git(crate::smith::Git::new())];
+
+        let packages = config.create_package_list(&smiths, &context).unwrap();
+
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].smith, "git");
+        assert_eq!(packages[0].package.source, "github:fork/plenary.nvim");
+    }
+}
@@ -0,0 +1,180 @@
+//! Patch files applied to packages after they are checked out
+//!
+//! Patch paths are relative to the config file which declares them, and are made absolute when the file is loaded, so
+//! that they still point at the right file after includes are merged. The contents of every patch are hashed by
+//! [`Config::load`], and the config is hashed with these hashes in place of the paths, which makes editing a patch
+//! change the hash of the config while moving the config does not.
+
+use super::{expand_env, Config, LoadConfigError};
+use crate::{manifest::Patch, package::Config as ConfigPackage};
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use std::{collections::BTreeMap, path::Path};
+
+/// Expand variables in the patch paths of packages and their dependencies, and make them relative to `base`
+fn resolve_paths(
+    packages: &mut BTreeMap<String, ConfigPackage>,
+    base: &Path,
+) -> Result<(), LoadConfigError> {
+    for (name, package) in packages {
+        for patch in &mut package.patches {
+            let expanded = expand_env(&patch.to_string_lossy()).map_err(|error| {
+                Report::new(LoadConfigError::Interpolate)
+                    .attach_printable(error)
+                    .attach_printable(format!("Package: {name}, field: patches"))
+            })?;
+
+            *patch = base.join(expanded);
+        }
+
+        resolve_paths(&mut package.dependencies, base)?;
+    }
+
+    Ok(())
+}
+
+/// Hash the patches of packages and their dependencies
+fn hash_patches(
+    packages: &BTreeMap<String, ConfigPackage>,
+    digests: &mut BTreeMap<String, String>,
+) -> Result<(), LoadConfigError> {
+    for (name, package) in packages {
+        for patch in &package.patches {
+            let contents = std::fs::read(patch)
+                .into_report()
+                .attach_printable_lazy(|| {
+                    format!("Package: {name}, patch path: {}", patch.display())
+                })
+                .change_context(LoadConfigError::Patch)?;
+
+            digests.insert(
+                patch.to_string_lossy().to_string(),
+                Patch::digest(&contents),
+            );
+        }
+
+        hash_patches(&package.dependencies, digests)?;
+    }
+
+    Ok(())
+}
+
+impl Config {
+    /// Make the patch paths of every package absolute, resolving relative paths against `base`
+    ///
+    /// # Errors
+    /// This function will return an error if a variable in a patch path is not set and has no default
+    pub fn resolve_patch_paths(&mut self, base: &Path) -> Result<(), LoadConfigError> {
+        resolve_paths(&mut self.packages, base)
+    }
+
+    /// Read and hash the patches of every package, storing the hashes in [`Config::patch_digests`]
+    ///
+    /// # Errors
+    /// This function will return an error if a patch cannot be read
+    pub fn hash_patches(&mut self) -> Result<(), LoadConfigError> {
+        let mut digests = BTreeMap::new();
+        hash_patches(&self.packages, &mut digests)?;
+        self.patch_digests = digests;
+
+        Ok(())
+    }
+
+    /// Get the packages left in the config with each patch path replaced by the hash of the patch, in declaration order
+    ///
+    /// Patch paths are absolute, so hashing them would give the same config a different hash in another directory.
+    /// Packages can be removed after loading by selecting a profile, and their patches should not change the hash.
+    pub(super) fn packages_with_patch_digests(&self) -> BTreeMap<String, ConfigPackage> {
+        fn replace(
+            packages: &mut BTreeMap<String, ConfigPackage>,
+            digests: &BTreeMap<String, String>,
+        ) {
+            for package in packages.values_mut() {
+                for patch in &mut package.patches {
+                    if let Some(digest) = digests.get(&*patch.to_string_lossy()) {
+                        *patch = digest.into();
+                    }
+                }
+
+                replace(&mut package.dependencies, digests);
+            }
+        }
+
+        let mut packages = self.packages.clone();
+        replace(&mut packages, &self.patch_digests);
+
+        packages
+    }
+
+    /// Get the patches of a package, with the hashes computed by [`Config::hash_patches`]
+    #[must_use]
+    pub fn patches(&self, package: &ConfigPackage) -> Vec<Patch> {
+        package
+            .patches
+            .iter()
+            .map(|patch| {
+                let path = patch.to_string_lossy().to_string();
+
+                Patch {
+                    sha256: self.patch_digests.get(&path).cloned().unwrap_or_default(),
+                    path,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patches_are_hashed() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("patches")).unwrap();
+        std::fs::write(dir.path().join("patches/fix.patch"), "fix\n").unwrap();
+        std::fs::write(
+            dir.path().join("packages.json"),
+            r#"{ "packages": { "github:nvim-lua/plenary.nvim": { "patches": ["patches/fix.patch"] } } }"#,
+        )
+        .unwrap();
+
+        let config = Config::load(&dir.path().join("packages.json")).unwrap();
+        let patches = config.patches(&config.packages["github:nvim-lua/plenary.nvim"]);
+
+        assert_eq!(
+            patches[0].path,
+            dir.path().join("patches/fix.patch").to_string_lossy()
+        );
+        // the SHA-256 of "fix\n"
+        assert_eq!(
+            patches[0].sha256,
+            "2619be9dc0356a196a8743f1f8eccfab471ac9f3e38f0c87f5bb052339f196a2"
+        );
+
+        let hash = config.stable_hash(&[]);
+        std::fs::write(dir.path().join("patches/fix.patch"), "fix 2\n").unwrap();
+        let config = Config::load(&dir.path().join("packages.json")).unwrap();
+        assert_ne!(config.stable_hash(&[]), hash);
+    }
+
+    #[test]
+    fn test_patch_paths_are_not_hashed() {
+        let hashes = (0..2)
+            .map(|_| {
+                let dir = tempfile::tempdir().unwrap();
+                std::fs::write(dir.path().join("fix.patch"), "fix\n").unwrap();
+                std::fs::write(
+                    dir.path().join("packages.json"),
+                    r#"{ "packages": { "github:nvim-lua/plenary.nvim": { "patches": ["fix.patch"] } } }"#,
+                )
+                .unwrap();
+
+                Config::load(&dir.path().join("packages.json"))
+                    .unwrap()
+                    .stable_hash(&[])
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(hashes[0], hashes[1]);
+    }
+}
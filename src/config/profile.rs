@@ -0,0 +1,198 @@
+//! Named profiles, which select a subset of the packages in a config
+//!
+//! A profile enables and disables groups of packages. Groups are defined in the config's `groups` section, and profiles
+//! can also refer to single packages by name.
+
+use super::{suggest, Config, Suggestion};
+use error_stack::{Context, Report, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+#[derive(
+    Debug, Default, Deserialize, Serialize, JsonSchema, Clone, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[serde(deny_unknown_fields)]
+/// A named selection of packages
+pub struct Profile {
+    /// The groups or packages to load. If this is empty, every package is loaded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub enable: Vec<String>,
+    /// The groups or packages not to load. This takes precedence over `enable`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disable: Vec<String>,
+}
+
+#[derive(Debug)]
+/// An error that can occur when selecting a profile
+pub enum ProfileError {
+    /// The profile does not exist
+    NotFound(String),
+    /// A profile or group refers to something that is neither a group nor a package
+    UnknownEntry(String),
+}
+
+impl Display for ProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound(name) => write!(f, "Profile {name} does not exist"),
+            Self::UnknownEntry(name) => {
+                write!(f, "{name} is neither a group nor a package")
+            }
+        }
+    }
+}
+
+impl Context for ProfileError {}
+
+/// Get the data directory for a profile
+///
+/// Each profile has its own package next to the data directory, with its own generations file and plugins. The
+/// `work` profile of `site/pack/alpacka` is installed to `site/pack/alpacka-work`, so Neovim finds its `start` and
+/// `opt` directories on the `packpath` like those of the data directory.
+#[must_use]
+pub fn profile_data_path(data_path: &Path, profile: &str) -> PathBuf {
+    let package = data_path
+        .file_name()
+        .map_or_else(|| "alpacka".into(), |name| name.to_string_lossy());
+
+    data_path.with_file_name(format!("{package}-{profile}"))
+}
+
+/// Create a report for an unknown name, with a suggestion if there is a similar name
+fn unknown_name<'a>(
+    error: ProfileError,
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Report<ProfileError> {
+    let report = Report::new(error);
+
+    match suggest(name, candidates) {
+        Some(suggestion) => report.attach_printable(Suggestion(suggestion.to_string())),
+        None => report,
+    }
+}
+
+impl Config {
+    /// Only keep the packages which are enabled by a profile.
+    ///
+    /// The groups and profiles are removed from the config afterwards, so the result only contains the packages of the profile.
+    ///
+    /// # Errors
+    /// This function will return an error if the profile does not exist, or if it refers to an unknown group or package
+    #[tracing::instrument(skip(self))]
+    pub fn select_profile(&mut self, name: &str) -> Result<(), ProfileError> {
+        let profile = self.profiles.remove(name).ok_or_else(|| {
+            unknown_name(
+                ProfileError::NotFound(name.to_string()),
+                name,
+                self.profiles.keys().map(String::as_str),
+            )
+        })?;
+
+        let enabled = if profile.enable.is_empty() {
+            self.packages.keys().cloned().collect()
+        } else {
+            self.expand_entries(&profile.enable)?
+        };
+        let disabled = self.expand_entries(&profile.disable)?;
+
+        self.packages
+            .retain(|package, _| enabled.contains(package) && !disabled.contains(package));
+        self.groups.clear();
+        self.profiles.clear();
+
+        Ok(())
+    }
+
+    /// Expand a list of group and package names into a set of package names
+    fn expand_entries(&self, entries: &[String]) -> Result<BTreeSet<String>, ProfileError> {
+        let mut packages = BTreeSet::new();
+
+        for entry in entries {
+            let members = self
+                .groups
+                .get(entry)
+                .map_or_else(|| std::slice::from_ref(entry), Vec::as_slice);
+
+            for member in members {
+                if !self.packages.contains_key(member) {
+                    return Err(unknown_name(
+                        ProfileError::UnknownEntry(member.clone()),
+                        member,
+                        self.groups
+                            .keys()
+                            .chain(self.packages.keys())
+                            .map(String::as_str),
+                    ));
+                }
+
+                packages.insert(member.clone());
+            }
+        }
+
+        Ok(packages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        Config::from_json(
+            r#"{
+                "packages": {
+                    "github:tpope/vim-fugitive": {},
+                    "github:neovim/nvim-lspconfig": {},
+                    "github:preservim/vim-pencil": {}
+                },
+                "groups": {
+                    "ide": ["github:neovim/nvim-lspconfig"],
+                    "writing": ["github:preservim/vim-pencil"]
+                },
+                "profiles": {
+                    "minimal": { "enable": ["github:tpope/vim-fugitive"] },
+                    "full": { "disable": ["writing"] }
+                }
+            }"#,
+            Path::new("packages.json"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_select_profile() {
+        let mut minimal = config();
+        minimal.select_profile("minimal").unwrap();
+        assert_eq!(
+            minimal.packages.keys().collect::<Vec<_>>(),
+            ["github:tpope/vim-fugitive"]
+        );
+
+        let mut full = config();
+        full.select_profile("full").unwrap();
+        assert_eq!(
+            full.packages.keys().collect::<Vec<_>>(),
+            ["github:neovim/nvim-lspconfig", "github:tpope/vim-fugitive"]
+        );
+    }
+
+    #[test]
+    fn test_profile_data_path() {
+        // Neovim only loads packages from `pack/*/start/*` and `pack/*/opt/*`
+        assert_eq!(
+            profile_data_path(Path::new("/data/nvim/site/pack/alpacka/"), "work"),
+            Path::new("/data/nvim/site/pack/alpacka-work")
+        );
+    }
+
+    #[test]
+    fn test_select_unknown_profile() {
+        assert!(config().select_profile("minimul").is_err());
+    }
+}
@@ -0,0 +1,61 @@
+//! JSON schema generation for the config format
+
+use super::Config;
+use crate::{package::VERSION_PREFIXES, smith::enums::Loaders};
+use schemars::schema_for;
+use serde_json::{json, Value};
+
+/// Generate a JSON schema for the config format.
+///
+/// On top of the schema derived from [`Config`], package names are restricted to the names that one of the given smiths
+/// can load, and versions are restricted to the allowed version prefixes.
+///
+/// # Panics
+/// Cannot panic, as a derived schema can always be converted to JSON
+#[must_use]
+pub fn schema(smiths: &[Loaders]) -> Value {
+    let mut schema = serde_json::to_value(schema_for!(Config)).unwrap();
+
+    let package_names = json!({
+        "anyOf": smiths
+            .iter()
+            .map(|smith| json!({
+                "description": format!("A package loaded by the {} smith", smith.name()),
+                "pattern": smith.package_name_pattern(),
+            }))
+            .collect::<Vec<_>>(),
+    });
+
+    let version_pattern = format!("^({}):.+$", VERSION_PREFIXES.join("|"));
+
+    schema["properties"]["packages"]["propertyNames"] = package_names.clone();
+    schema["properties"]["overrides"]["propertyNames"] = package_names.clone();
+
+    let package = &mut schema["definitions"]["PackageConfig"]["properties"];
+    package["dependencies"]["propertyNames"] = package_names;
+    package["version"]["pattern"] = json!(version_pattern);
+
+    schema["definitions"]["Override"]["properties"]["version"]["pattern"] = json!(version_pattern);
+
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::smith::{Git, Smith};
+
+    #[test]
+    fn test_schema() {
+        let schema = schema(&[Loaders::Git(Git::new())]);
+
+        assert_eq!(
+            schema["properties"]["packages"]["propertyNames"]["anyOf"][0]["pattern"],
+            Git::new().package_name_pattern()
+        );
+        assert_eq!(
+            schema["definitions"]["PackageConfig"]["properties"]["version"]["pattern"],
+            "^(tag|commit|branch):.+$"
+        );
+    }
+}
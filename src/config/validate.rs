@@ -0,0 +1,168 @@
+//! Strict parsing of config files
+//!
+//! Unknown fields and malformed versions are rejected instead of being ignored.
+//! Errors carry a [`Location`] pointing at the offending part of the file, and a suggestion when the mistake looks like a typo.
+
+use super::{Config, LoadConfigError};
+use error_stack::{Report, Result};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+/// The minimum similarity for a value to be suggested as a replacement
+const SUGGESTION_THRESHOLD: f64 = 0.7;
+
+/// The number of characters of a value per edit allowed in a suggested replacement
+const CHARACTERS_PER_EDIT: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A location inside of a config file
+///
+/// This is attached to config errors, and can be retrieved from the report with [`Report::downcast_ref`]
+pub struct Location {
+    /// The path of the config file
+    pub path: PathBuf,
+    /// The line of the error, starting at 1
+    pub line: usize,
+    /// The column of the error, starting at 1
+    pub column: usize,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.path.display(), self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A suggested replacement for a mistyped value
+pub struct Suggestion(pub String);
+
+impl Display for Suggestion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "help: did you mean `{}`?", self.0)
+    }
+}
+
+/// Find the candidate most similar to `value`, if any of them is similar enough
+///
+/// Candidates within a few edits of `value` are preferred, so that typos such as swapped letters (`tga` for `tag`) are
+/// found even in short words, where their Jaro-Winkler similarity is low.
+pub fn suggest<'a>(value: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let candidates = candidates.into_iter().collect::<Vec<_>>();
+    let max_edits = (value.chars().count() / CHARACTERS_PER_EDIT).max(1);
+
+    let closest = candidates
+        .iter()
+        .map(|candidate| (strsim::osa_distance(value, candidate), *candidate))
+        .filter(|(edits, _)| *edits <= max_edits)
+        .min_by_key(|(edits, _)| *edits)
+        .map(|(_, candidate)| candidate);
+
+    closest.or_else(|| {
+        candidates
+            .into_iter()
+            .map(|candidate| (strsim::jaro_winkler(value, candidate), candidate))
+            .filter(|(similarity, _)| *similarity > SUGGESTION_THRESHOLD)
+            .max_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, candidate)| candidate)
+    })
+}
+
+/// Find a suggestion for an unknown field or variant in a serde error message
+///
+/// Serde formats these errors as "unknown field `name`, expected one of `a`, `b`", so both the unknown value and the
+/// candidates can be read back from the message.
+pub fn suggest_from_message(message: &str) -> Option<Suggestion> {
+    let (_, rest) = message
+        .split_once("unknown field `")
+        .or_else(|| message.split_once("unknown variant `"))?;
+    let (unknown, rest) = rest.split_once('`')?;
+    let (_, expected) = rest.split_once("expected ")?;
+
+    let candidates = expected.split('`').skip(1).step_by(2);
+
+    suggest(unknown, candidates).map(|candidate| Suggestion(candidate.to_string()))
+}
+
+/// Attach a suggestion to a report if the error message contains a mistyped value
+pub fn attach_suggestion<C>(report: Report<C>, message: &str) -> Report<C> {
+    match suggest_from_message(message) {
+        Some(suggestion) => report.attach_printable(suggestion),
+        None => report,
+    }
+}
+
+impl Config {
+    /// Parse a JSON config file.
+    ///
+    /// `path` is only used for error reporting.
+    ///
+    /// # Errors
+    /// This function will return an error if the JSON is invalid, contains unknown fields, or contains a malformed version.
+    /// The error will have a [`Location`] attached.
+    pub fn from_json(source: &str, path: &Path) -> Result<Self, LoadConfigError> {
+        serde_json::from_str(source).map_err(|error| {
+            let location = Location {
+                path: path.to_path_buf(),
+                line: error.line(),
+                column: error.column(),
+            };
+            let message = error.to_string();
+
+            let report = Report::new(error)
+                .change_context(LoadConfigError::Parse)
+                .attach_printable(location);
+
+            attach_suggestion(report, &message)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Config, LoadConfigError> {
+        Config::from_json(source, Path::new("packages.json"))
+    }
+
+    #[test]
+    fn test_unknown_field_is_rejected() {
+        let report = parse(
+            r#"{
+  "packages": {
+    "github:nyoom-engineering/nyoom.nvim": {
+      "opt": true
+    }
+  }
+}"#,
+        )
+        .unwrap_err();
+
+        let location = report.downcast_ref::<Location>().unwrap();
+        assert_eq!(location.line, 4);
+
+        let suggestion = report.downcast_ref::<Suggestion>().unwrap();
+        assert_eq!(suggestion.0, "optional");
+    }
+
+    #[test]
+    fn test_malformed_version_is_rejected() {
+        assert!(parse(r#"{ "packages": { "github:a/b": { "version": "v1.0" } } }"#).is_err());
+        assert!(parse(r#"{ "packages": { "github:a/b": { "version": "tga:v1.0" } } }"#).is_err());
+        assert!(parse(r#"{ "packages": { "github:a/b": { "version": "tag:" } } }"#).is_err());
+        assert!(parse(r#"{ "packages": { "github:a/b": { "version": "tag:v1.0" } } }"#).is_ok());
+    }
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(
+            suggest("opt", ["optional", "version", "rename"]),
+            Some("optional")
+        );
+        assert_eq!(suggest("tga", ["tag", "commit", "branch"]), Some("tag"));
+        assert_eq!(suggest("xyz", ["optional", "version", "rename"]), None);
+    }
+}
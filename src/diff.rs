@@ -0,0 +1,231 @@
+//! A module which compares the manifests of two generations
+//!
+//! Plugins are matched by the name they have in the config, so a plugin which moved to another source is reported as
+//! a change of revision rather than as a removed and an added plugin.
+
+use crate::manifest::{Manifest, Plugin};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+/// Where a plugin is placed in the data directory, which decides whether neovim loads it on startup
+pub enum Placement {
+    /// In `start`, loaded on startup
+    Start,
+    /// In `opt`, loaded with `:packadd`
+    Opt,
+}
+
+impl Placement {
+    const fn of(plugin: &Plugin) -> Self {
+        if plugin.optional {
+            Self::Opt
+        } else {
+            Self::Start
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+/// A change to a plugin which is in both manifests
+pub enum Change {
+    /// The plugin is at another revision, such as a git commit hash
+    Revision { from: String, to: String },
+    /// The plugin moved between `start` and `opt`
+    Placement { from: Placement, to: Placement },
+    /// The plugin is loaded under another name
+    Rename {
+        from: Option<String>,
+        to: Option<String>,
+    },
+    /// The build command of the plugin changed
+    Build { from: String, to: String },
+    /// The plugin depends on other packages
+    Dependencies {
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// The changes to a plugin which is in both manifests
+pub struct PluginDiff {
+    /// The name of the package, as used in the config
+    pub name: String,
+    /// The changes, in the order of the fields of [`Change`]
+    pub changes: Vec<Change>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+/// The differences between two manifests
+pub struct ManifestDiff {
+    /// The plugins which are only in the newer manifest, as named in the config
+    pub added: Vec<String>,
+    /// The plugins which are only in the older manifest, as named in the config
+    pub removed: Vec<String>,
+    /// The plugins which are in both manifests, but changed
+    pub changed: Vec<PluginDiff>,
+}
+
+impl ManifestDiff {
+    /// Check if the manifests install the same plugins in the same way
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Get the changes between two versions of the same plugin
+fn changes(from: &Plugin, to: &Plugin) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    if from.loader_data.revision() != to.loader_data.revision() {
+        changes.push(Change::Revision {
+            from: from.loader_data.revision().to_string(),
+            to: to.loader_data.revision().to_string(),
+        });
+    }
+
+    if from.optional != to.optional {
+        changes.push(Change::Placement {
+            from: Placement::of(from),
+            to: Placement::of(to),
+        });
+    }
+
+    if from.rename != to.rename {
+        changes.push(Change::Rename {
+            from: from.rename.clone(),
+            to: to.rename.clone(),
+        });
+    }
+
+    if from.build != to.build {
+        changes.push(Change::Build {
+            from: from.build.clone(),
+            to: to.build.clone(),
+        });
+    }
+
+    let only_in = |a: &Plugin, b: &Plugin| -> Vec<String> {
+        a.dependencies
+            .iter()
+            .filter(|dependency| !b.dependencies.contains(dependency))
+            .cloned()
+            .collect()
+    };
+    let added = only_in(to, from);
+    let removed = only_in(from, to);
+
+    if !added.is_empty() || !removed.is_empty() {
+        changes.push(Change::Dependencies { added, removed });
+    }
+
+    changes
+}
+
+/// Find a plugin of a manifest by the name it has in the config
+fn find<'a>(manifest: &'a Manifest, name: &str) -> Option<&'a Plugin> {
+    manifest
+        .plugins
+        .iter()
+        .find(|plugin| plugin.unresolved_name == name)
+}
+
+/// Compare two manifests, from the older to the newer one
+///
+/// Added and changed plugins are in the order of the newer manifest, and removed plugins in the order of the older one
+#[must_use]
+pub fn diff(from: &Manifest, to: &Manifest) -> ManifestDiff {
+    let mut diff = ManifestDiff::default();
+
+    for plugin in &to.plugins {
+        match find(from, &plugin.unresolved_name) {
+            None => diff.added.push(plugin.unresolved_name.clone()),
+            Some(old) => {
+                let changes = changes(old, plugin);
+
+                if !changes.is_empty() {
+                    diff.changed.push(PluginDiff {
+                        name: plugin.unresolved_name.clone(),
+                        changes,
+                    });
+                }
+            }
+        }
+    }
+
+    diff.removed = from
+        .plugins
+        .iter()
+        .filter(|plugin| find(to, &plugin.unresolved_name).is_none())
+        .map(|plugin| plugin.unresolved_name.clone())
+        .collect();
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::fixtures::{manifest, plugin};
+
+    #[test]
+    fn test_diff() {
+        let from = manifest(vec![
+            plugin("plenary.nvim", "aaaa"),
+            Plugin {
+                dependencies: vec!["github:nvim-lua/popup.nvim".to_string()],
+                ..plugin("telescope.nvim", "bbbb")
+            },
+            plugin("popup.nvim", "cccc"),
+        ]);
+        let to = manifest(vec![
+            plugin("plenary.nvim", "aaaa"),
+            Plugin {
+                optional: true,
+                rename: Some("telescope".to_string()),
+                build: "make".to_string(),
+                dependencies: vec!["github:nvim-lua/plenary.nvim".to_string()],
+                ..plugin("telescope.nvim", "dddd")
+            },
+            plugin("lspconfig.nvim", "eeee"),
+        ]);
+
+        assert_eq!(
+            diff(&from, &to),
+            ManifestDiff {
+                added: vec!["github:nvim-lua/lspconfig.nvim".to_string()],
+                removed: vec!["github:nvim-lua/popup.nvim".to_string()],
+                changed: vec![PluginDiff {
+                    name: "github:nvim-lua/telescope.nvim".to_string(),
+                    changes: vec![
+                        Change::Revision {
+                            from: "bbbb".to_string(),
+                            to: "dddd".to_string(),
+                        },
+                        Change::Placement {
+                            from: Placement::Start,
+                            to: Placement::Opt,
+                        },
+                        Change::Rename {
+                            from: None,
+                            to: Some("telescope".to_string()),
+                        },
+                        Change::Build {
+                            from: String::new(),
+                            to: "make".to_string(),
+                        },
+                        Change::Dependencies {
+                            added: vec!["github:nvim-lua/plenary.nvim".to_string()],
+                            removed: vec!["github:nvim-lua/popup.nvim".to_string()],
+                        },
+                    ],
+                }],
+            }
+        );
+
+        assert!(diff(&to, &to).is_empty());
+    }
+}
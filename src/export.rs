@@ -0,0 +1,420 @@
+//! A module which exports manifests to the lockfile formats of other tools
+//!
+//! A manifest can be exported to a lazy.nvim `lazy-lock.json`, a Neovim `vim.pack` lockfile, or a Nix expression
+//! which fetches every plugin with `fetchgit`. The hashes in the Nix expression are computed from the local checkouts
+//! of the plugins, so they can be exported without network access. Patched plugins are wrapped in `applyPatches` in
+//! the Nix expression, while the lockfiles of lazy.nvim and `vim.pack` cannot express patches, so they refuse to export
+//! them.
+
+use crate::{
+    manifest::{Manifest, Plugin},
+    smith::{enums::Inputs, GitInput},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use error_stack::{Context, IntoReport, Report, Result, ResultExt};
+use git2::{ObjectType, Oid, Repository, Tree};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::{Display, Write},
+    path::Path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A lockfile format to export to
+pub enum ExportFormat {
+    /// A lazy.nvim `lazy-lock.json` lockfile
+    LazyLock,
+    /// A Neovim `vim.pack` lockfile
+    VimPack,
+    /// A Nix expression using `fetchgit`
+    Nix,
+}
+
+#[derive(Debug)]
+/// An error that can occur when exporting a manifest
+pub enum ExportError {
+    /// The lockfile could not be serialized
+    Serialize,
+    /// The local checkout of a plugin could not be hashed
+    Hash(String),
+    /// A plugin has patches, which the lockfile format cannot express
+    Patched(String),
+}
+
+impl Display for ExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Serialize => f.write_str("Failed to serialize lockfile"),
+            Self::Hash(plugin) => write!(f, "Failed to hash the checkout of plugin {plugin}"),
+            Self::Patched(plugin) => write!(
+                f,
+                "Plugin {plugin} has patches, which this lockfile format cannot express. Export to Nix instead, or remove the patches"
+            ),
+        }
+    }
+}
+
+impl Context for ExportError {}
+
+/// Get the git input of a plugin
+const fn git_input(plugin: &Plugin) -> &GitInput {
+    match &plugin.loader_data {
+        Inputs::Git(input) => input,
+    }
+}
+
+/// Check that no plugin has patches, for lockfile formats which would silently drop them
+fn reject_patches(manifest: &Manifest) -> Result<(), ExportError> {
+    if let Some(plugin) = manifest
+        .plugins
+        .iter()
+        .find(|plugin| !plugin.patches.is_empty())
+    {
+        return Err(Report::new(ExportError::Patched(
+            plugin.unresolved_name.clone(),
+        )));
+    }
+
+    Ok(())
+}
+
+/// Get the name of the directory a plugin is installed to, which other package managers use as the plugin's name
+fn directory_name(plugin: &Plugin) -> &str {
+    plugin.rename.as_ref().unwrap_or(&plugin.name)
+}
+
+impl ExportFormat {
+    /// Export a manifest to this format
+    ///
+    /// `data_path` is the data directory the manifest was installed to, and is used to find the checkouts for [`ExportFormat::Nix`]
+    ///
+    /// # Errors
+    /// This function will return an error if the lockfile cannot be serialized, or if a checkout cannot be hashed
+    pub fn export(self, manifest: &Manifest, data_path: &Path) -> Result<String, ExportError> {
+        match self {
+            Self::LazyLock => lazy_lock(manifest),
+            Self::VimPack => vim_pack_lock(manifest),
+            Self::Nix => nix(manifest, data_path),
+        }
+    }
+}
+
+/// Export a manifest to a lazy.nvim `lazy-lock.json`
+///
+/// Plugins are keyed by their directory name and pinned to their commit. Manifests do not record branches, so lazy.nvim
+/// uses each plugin's default branch.
+///
+/// # Errors
+/// This function will return an error if a plugin has patches, or if the lockfile cannot be serialized
+pub fn lazy_lock(manifest: &Manifest) -> Result<String, ExportError> {
+    reject_patches(manifest)?;
+
+    let mut plugins: Vec<_> = manifest.plugins.iter().collect();
+    plugins.sort_by(|a, b| directory_name(a).cmp(directory_name(b)));
+
+    // lazy.nvim writes one plugin per line, which keeps diffs of the lockfile small
+    let lines = plugins
+        .into_iter()
+        .map(|plugin| {
+            let name = serde_json::to_string(directory_name(plugin))
+                .into_report()
+                .change_context(ExportError::Serialize)?;
+            let commit = serde_json::to_string(git_input(plugin).commit_hash())
+                .into_report()
+                .change_context(ExportError::Serialize)?;
+
+            Ok(format!("  {name}: {{ \"commit\": {commit} }}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(format!("{{\n{}\n}}\n", lines.join(",\n")))
+}
+
+/// Export a manifest to a Neovim `vim.pack` lockfile
+///
+/// # Errors
+/// This function will return an error if a plugin has patches, or if the lockfile cannot be serialized
+pub fn vim_pack_lock(manifest: &Manifest) -> Result<String, ExportError> {
+    reject_patches(manifest)?;
+
+    let plugins: Map<String, Value> = manifest
+        .plugins
+        .iter()
+        .map(|plugin| {
+            let input = git_input(plugin);
+
+            (
+                directory_name(plugin).to_string(),
+                json!({ "rev": input.commit_hash(), "src": input.remote() }),
+            )
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&json!({ "plugins": plugins }))
+        .into_report()
+        .change_context(ExportError::Serialize)
+        .map(|lockfile| lockfile + "\n")
+}
+
+/// Export a manifest to a Nix expression, which is a function from `fetchgit` to an attribute set of plugin sources
+///
+/// Patched plugins are wrapped in `applyPatches`, which is then taken as an argument too. The hash of a plugin is the
+/// hash of its commit, which is what `fetchgit` fetches before the patches are applied.
+///
+/// # Errors
+/// This function will return an error if the checkout of a plugin cannot be hashed
+pub fn nix(manifest: &Manifest, data_path: &Path) -> Result<String, ExportError> {
+    let mut plugins: Vec<_> = manifest.plugins.iter().collect();
+    plugins.sort_by(|a, b| directory_name(a).cmp(directory_name(b)));
+
+    let mut expression = if plugins.iter().any(|plugin| !plugin.patches.is_empty()) {
+        String::from("{ fetchgit, applyPatches }:\n{\n")
+    } else {
+        String::from("{ fetchgit }:\n{\n")
+    };
+
+    for plugin in plugins {
+        let input = git_input(plugin);
+        let hash = nar_hash(&plugin.install_path(data_path), input.commit_hash())
+            .change_context_lazy(|| ExportError::Hash(plugin.name.clone()))?;
+
+        let name = nix_string(directory_name(plugin))?;
+        let url = nix_string(input.remote())?;
+        let fetchgit = |indent: &str| {
+            format!(
+                "fetchgit {{\n{indent}  url = {url};\n{indent}  rev = \"{}\";\n{indent}  hash = \"{hash}\";\n{indent}  fetchSubmodules = false;\n{indent}}}",
+                input.commit_hash()
+            )
+        };
+
+        if plugin.patches.is_empty() {
+            let _ = writeln!(expression, "  {name} = {};", fetchgit("  "));
+            continue;
+        }
+
+        // patch paths are absolute, so they are turned into Nix paths from the root
+        let patches = plugin
+            .patches
+            .iter()
+            .map(|patch| nix_string(&patch.path).map(|path| format!("(/. + {path})")))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let _ = writeln!(
+            expression,
+            "  {name} = applyPatches {{\n    name = {name};\n    src = {};\n    patches = [ {} ];\n  }};",
+            fetchgit("    "),
+            patches.join(" ")
+        );
+    }
+
+    expression.push_str("}\n");
+
+    Ok(expression)
+}
+
+/// Quote a Nix string
+///
+/// JSON escapes are also valid in Nix strings, except that `${` starts an interpolation in Nix
+fn nix_string(value: &str) -> Result<String, ExportError> {
+    serde_json::to_string(value)
+        .into_report()
+        .change_context(ExportError::Serialize)
+        .map(|string| string.replace("${", "\\${"))
+}
+
+/// Compute the NAR hash of a commit in a local repository, in SRI format
+///
+/// The hash is computed from the commit's tree rather than the working directory, so files created by build scripts
+/// are not included. `.git` is never part of the tree, and submodules are empty directories, which matches what
+/// `fetchgit` produces with `fetchSubmodules = false`.
+///
+/// # Errors
+/// This function will return an error if the repository or commit cannot be found
+pub fn nar_hash(repo_path: &Path, commit: &str) -> Result<String, ExportError> {
+    let hash_error = || ExportError::Hash(repo_path.display().to_string());
+
+    let repo = Repository::open(repo_path)
+        .into_report()
+        .change_context_lazy(hash_error)?;
+    let tree = Oid::from_str(commit)
+        .and_then(|oid| repo.find_commit(oid))
+        .and_then(|commit| commit.tree())
+        .into_report()
+        .attach_printable_lazy(|| format!("Commit: {commit}"))
+        .change_context_lazy(hash_error)?;
+
+    let mut hasher = Sha256::new();
+    nar_string(&mut hasher, b"nix-archive-1");
+    nar_tree(&repo, &tree, &mut hasher).change_context_lazy(hash_error)?;
+
+    Ok(format!("sha256-{}", STANDARD.encode(hasher.finalize())))
+}
+
+/// Write a string in the NAR format: its length, its bytes, and padding to a multiple of 8 bytes
+fn nar_string(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+    hasher.update(&[0_u8; 8][..(8 - bytes.len() % 8) % 8]);
+}
+
+/// Write a git tree as a NAR directory
+fn nar_tree(repo: &Repository, tree: &Tree, hasher: &mut Sha256) -> Result<(), git2::Error> {
+    // NAR entries are sorted by name, while git sorts directories as if they ended in `/`
+    let mut entries: Vec<_> = tree.iter().collect();
+    entries.sort_by(|a, b| a.name_bytes().cmp(b.name_bytes()));
+
+    for token in ["(", "type", "directory"] {
+        nar_string(hasher, token.as_bytes());
+    }
+
+    for entry in entries {
+        for token in ["entry", "(", "name"] {
+            nar_string(hasher, token.as_bytes());
+        }
+        nar_string(hasher, entry.name_bytes());
+        nar_string(hasher, b"node");
+
+        match entry.kind() {
+            Some(ObjectType::Tree) => {
+                let tree = repo.find_tree(entry.id()).into_report()?;
+                nar_tree(repo, &tree, hasher)?;
+            }
+            Some(ObjectType::Blob) => {
+                let blob = repo.find_blob(entry.id()).into_report()?;
+
+                nar_string(hasher, b"(");
+                nar_string(hasher, b"type");
+
+                match entry.filemode() {
+                    0o120_000 => {
+                        nar_string(hasher, b"symlink");
+                        nar_string(hasher, b"target");
+                    }
+                    0o100_755 => {
+                        for token in ["regular", "executable", ""] {
+                            nar_string(hasher, token.as_bytes());
+                        }
+                        nar_string(hasher, b"contents");
+                    }
+                    _ => {
+                        nar_string(hasher, b"regular");
+                        nar_string(hasher, b"contents");
+                    }
+                }
+
+                nar_string(hasher, blob.content());
+                nar_string(hasher, b")");
+            }
+            // submodules are not fetched, so they are empty directories
+            _ => {
+                for token in ["(", "type", "directory", ")"] {
+                    nar_string(hasher, token.as_bytes());
+                }
+            }
+        }
+
+        nar_string(hasher, b")");
+    }
+
+    nar_string(hasher, b")");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::Patch;
+
+    fn manifest() -> Manifest {
+        Manifest::new(
+            "0.9.0".to_string(),
+            vec![Plugin {
+                name: "nvim".to_string(),
+                unresolved_name: "github:catppuccin/nvim".to_string(),
+                rename: Some("catppuccin".to_string()),
+                optional: false,
+                dependencies: vec![],
+                smith: "git".to_string(),
+                build: String::new(),
+                loader_data: Inputs::Git(GitInput::new(
+                    "0123456789abcdef0123456789abcdef01234567".to_string(),
+                    "https://github.com/catppuccin/nvim.git".to_string(),
+                )),
+                overridden: None,
+                patches: Vec::new(),
+                resolved_from: None,
+            }],
+            vec![],
+        )
+    }
+
+    #[test]
+    fn test_lockfiles() {
+        assert_eq!(
+            lazy_lock(&manifest()).unwrap(),
+            "{\n  \"catppuccin\": { \"commit\": \"0123456789abcdef0123456789abcdef01234567\" }\n}\n"
+        );
+
+        let vim_pack: Value = serde_json::from_str(&vim_pack_lock(&manifest()).unwrap()).unwrap();
+        assert_eq!(
+            vim_pack["plugins"]["catppuccin"]["src"],
+            "https://github.com/catppuccin/nvim.git"
+        );
+    }
+
+    /// Create a repository with a single commit containing the file `a`
+    fn repository(path: &Path) -> Oid {
+        let repo = Repository::init(path).unwrap();
+
+        std::fs::write(path.join("a"), "hello\n").unwrap();
+
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("a")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("alpacka", "alpacka@example.com").unwrap();
+
+        repo.commit(None, &signature, &signature, "init", &tree, &[])
+            .unwrap()
+    }
+
+    #[test]
+    fn test_patched_plugins() {
+        let data_path = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(data_path.path().join("start/catppuccin")).unwrap();
+        let commit = repository(&data_path.path().join("start/catppuccin"));
+
+        let mut manifest = manifest();
+        let plugin = &mut manifest.plugins[0];
+        plugin.loader_data = Inputs::Git(GitInput::new(
+            commit.to_string(),
+            "https://github.com/catppuccin/nvim.git".to_string(),
+        ));
+        plugin.patches = vec![Patch {
+            path: "/patches/fix.patch".to_string(),
+            sha256: String::new(),
+        }];
+
+        let error = lazy_lock(&manifest).unwrap_err();
+        assert!(matches!(error.current_context(), ExportError::Patched(_)));
+        assert!(vim_pack_lock(&manifest).is_err());
+
+        let expression = nix(&manifest, data_path.path()).unwrap();
+        assert!(expression.starts_with("{ fetchgit, applyPatches }:"));
+        assert!(expression.contains("\"catppuccin\" = applyPatches {"));
+        assert!(expression.contains("patches = [ (/. + \"/patches/fix.patch\") ];"));
+    }
+
+    #[test]
+    fn test_nar_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let commit = repository(dir.path());
+
+        // the hash of the NAR of a directory containing only `a`
+        assert_eq!(
+            nar_hash(dir.path(), &commit.to_string()).unwrap(),
+            "sha256-lYm+o5HTptO6iNGG6L41EMhGGyh2L9gt5P7stJH8gqI="
+        );
+    }
+}
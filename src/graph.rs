@@ -0,0 +1,500 @@
+//! A module which builds the dependency graph of a config
+//!
+//! Every package is a node, keyed by its name. A package which is requested several times, as a top level package or
+//! as a dependency of other packages, is merged into a single node, so it is resolved and loaded once.
+//! Requests which set the same field to different values are reported as conflicts, and dependency cycles are rejected.
+//! The config's overrides are applied to every node, so they replace a package's source or version wherever it appears.
+
+use crate::{
+    condition::ConditionContext,
+    config::Config,
+    package::{Config as ConfigPackage, Package, WithSmith},
+    smith::enums::Loaders,
+};
+use error_stack::{Report, Result};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    iter::once,
+};
+
+#[derive(Debug)]
+/// An error that can occur when building a dependency graph
+pub enum GraphError {
+    /// No smith can load the package
+    NoLoaderFound(String),
+    /// Packages are requested with conflicting fields. A [`Conflict`] is attached to the report for every conflict
+    Conflict,
+    /// The dependencies form a cycle. The [`Cycle`] is attached to the report
+    Cycle,
+}
+
+impl Display for GraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoLoaderFound(name) => write!(f, "No loader found for package {name}"),
+            Self::Conflict => {
+                f.write_str("Packages are requested with conflicting versions or options")
+            }
+            Self::Cycle => f.write_str("Package dependencies form a cycle"),
+        }
+    }
+}
+
+impl error_stack::Context for GraphError {}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// What requested a package
+pub enum Requester {
+    /// The package is declared at the top level of the config
+    Config,
+    /// The package is a dependency of another package
+    Package(String),
+}
+
+impl Display for Requester {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Config => f.write_str("the config"),
+            Self::Package(name) => f.write_str(name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A field of a package which its requesters set to different values
+pub struct Conflict {
+    /// The name of the package
+    pub package: String,
+    /// The name of the conflicting field
+    pub field: &'static str,
+    /// Every requester which set the field, and the value it asked for
+    pub requests: Vec<(Requester, String)>,
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is requested with conflicting {} values:",
+            self.package, self.field
+        )?;
+
+        for (requester, value) in &self.requests {
+            write!(f, "\n  - `{value}` by {requester}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A dependency cycle, which starts and ends with the same package
+pub struct Cycle(pub Vec<String>);
+
+impl Display for Cycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Dependency cycle: {}", self.0.join(" -> "))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A package in the dependency graph
+pub struct Node {
+    /// The name of the smith used to handle this package
+    pub smith: String,
+    /// The name the package is resolved from, which is replaced by an override's source
+    pub source: String,
+    /// The declaration of the package, merged from every request.
+    ///
+    /// Its dependencies are the union of the dependencies of every request. Only their names are meaningful, as each
+    /// dependency is merged into its own node.
+    pub config: ConfigPackage,
+    /// Everything which requested this package
+    pub requested_by: BTreeSet<Requester>,
+}
+
+#[derive(Debug, Clone, Default)]
+/// The dependency graph of a config, with every package appearing once
+pub struct DependencyGraph {
+    nodes: BTreeMap<String, Node>,
+    /// The package names, sorted so that every package comes after its dependencies
+    order: Vec<String>,
+}
+
+/// Every declaration of every package, and what declared it
+type Requests<'a> = BTreeMap<&'a str, Vec<(Requester, &'a ConfigPackage)>>;
+
+/// Collect the declarations of packages and their dependencies, skipping those whose condition does not pass
+fn collect_requests<'a>(
+    packages: &'a BTreeMap<String, ConfigPackage>,
+    requester: &Requester,
+    context: &ConditionContext,
+    requests: &mut Requests<'a>,
+) {
+    for (name, package) in packages {
+        if !package.should_load(name, context) {
+            continue;
+        }
+
+        requests
+            .entry(name)
+            .or_default()
+            .push((requester.clone(), package));
+
+        collect_requests(
+            &package.dependencies,
+            &Requester::Package(name.clone()),
+            context,
+            requests,
+        );
+    }
+}
+
+/// Merge a field of every declaration of a package
+///
+/// Declarations which leave the field unset accept any value, so only declarations which set it to different values conflict
+fn merge_field<'a, T: Ord + Clone + 'a>(
+    name: &str,
+    field: &'static str,
+    requests: &[(Requester, &'a ConfigPackage)],
+    get: impl Fn(&'a ConfigPackage) -> Option<&'a T>,
+    describe: impl Fn(&T) -> String,
+    conflicts: &mut Vec<Conflict>,
+) -> Option<T> {
+    let set: Vec<_> = requests
+        .iter()
+        .filter_map(|(requester, package)| get(package).map(|value| (requester, value)))
+        .collect();
+    let values: BTreeSet<_> = set.iter().map(|(_, value)| *value).collect();
+
+    if values.len() > 1 {
+        let mut requests: Vec<_> = set
+            .into_iter()
+            .map(|(requester, value)| (requester.clone(), describe(value)))
+            .collect();
+        requests.sort();
+        requests.dedup();
+
+        conflicts.push(Conflict {
+            package: name.to_string(),
+            field,
+            requests,
+        });
+    }
+
+    values.into_iter().next().cloned()
+}
+
+impl DependencyGraph {
+    /// Build the dependency graph of a config
+    ///
+    /// Packages and dependencies whose condition does not pass are left out.
+    /// A package is only optional if every declaration marks it as optional, as a package loaded on startup needs its
+    /// dependencies to be loaded too.
+    ///
+    /// # Errors
+    /// This function will return an error if no smith can load a package, if a package is requested with conflicting
+    /// fields, or if the dependencies form a cycle
+    #[tracing::instrument(skip(config))]
+    pub fn new(
+        config: &Config,
+        smiths: &[Loaders],
+        context: &ConditionContext,
+    ) -> Result<Self, GraphError> {
+        let mut requests = Requests::new();
+        collect_requests(&config.packages, &Requester::Config, context, &mut requests);
+
+        let mut nodes = BTreeMap::new();
+        let mut conflicts = Vec::new();
+
+        for (name, requests) in requests {
+            let package_override = config.overrides.get(name);
+            let source = package_override
+                .and_then(|package_override| package_override.source.clone())
+                .unwrap_or_else(|| name.to_string());
+
+            let smith = smiths
+                .iter()
+                .find(|smith| smith.get_package_name(&source).is_some())
+                .ok_or_else(|| Report::new(GraphError::NoLoaderFound(source.clone())))?;
+
+            let mut dependencies = BTreeMap::new();
+            for (_, package) in &requests {
+                for (dependency, declaration) in &package.dependencies {
+                    if declaration.should_load(dependency, context) {
+                        dependencies
+                            .entry(dependency.clone())
+                            .or_insert_with(|| declaration.clone());
+                    }
+                }
+            }
+
+            // an overridden version replaces the requested ones, so they cannot conflict
+            let version = package_override
+                .and_then(|package_override| package_override.version.clone())
+                .or_else(|| {
+                    merge_field(
+                        name,
+                        "version",
+                        &requests,
+                        |package| package.version.as_ref(),
+                        ToString::to_string,
+                        &mut conflicts,
+                    )
+                });
+
+            let config = ConfigPackage {
+                optional: requests
+                    .iter()
+                    .all(|(_, package)| package.optional.unwrap_or(false))
+                    .then_some(true),
+                version,
+                rename: merge_field(
+                    name,
+                    "rename",
+                    &requests,
+                    |package| package.rename.as_ref(),
+                    ToString::to_string,
+                    &mut conflicts,
+                ),
+                build: merge_field(
+                    name,
+                    "build",
+                    &requests,
+                    |package| package.build.as_ref(),
+                    ToString::to_string,
+                    &mut conflicts,
+                ),
+                dependencies,
+                when: None,
+                disabled: None,
+                patches: merge_field(
+                    name,
+                    "patches",
+                    &requests,
+                    |package| (!package.patches.is_empty()).then_some(&package.patches),
+                    |patches| {
+                        patches
+                            .iter()
+                            .map(|patch| patch.display().to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    },
+                    &mut conflicts,
+                )
+                .unwrap_or_default(),
+            };
+
+            nodes.insert(
+                name.to_string(),
+                Node {
+                    smith: smith.name(),
+                    source,
+                    config,
+                    requested_by: requests
+                        .into_iter()
+                        .map(|(requester, _)| requester)
+                        .collect(),
+                },
+            );
+        }
+
+        if !conflicts.is_empty() {
+            return Err(conflicts
+                .into_iter()
+                .fold(Report::new(GraphError::Conflict), Report::attach_printable));
+        }
+
+        let mut graph = Self {
+            nodes,
+            order: Vec::new(),
+        };
+        graph.order = graph.sort()?;
+
+        Ok(graph)
+    }
+
+    /// Sort the packages so that every package comes after its dependencies
+    ///
+    /// # Errors
+    /// This function will return an error if the dependencies form a cycle
+    fn sort(&self) -> Result<Vec<String>, GraphError> {
+        fn visit<'a>(
+            graph: &'a DependencyGraph,
+            name: &'a str,
+            path: &mut Vec<&'a str>,
+            order: &mut Vec<String>,
+        ) -> Result<(), GraphError> {
+            if order.iter().any(|done| done == name) {
+                return Ok(());
+            }
+
+            if let Some(start) = path.iter().position(|package| *package == name) {
+                let cycle = path[start..]
+                    .iter()
+                    .chain(once(&name))
+                    .map(ToString::to_string)
+                    .collect();
+
+                return Err(Report::new(GraphError::Cycle).attach_printable(Cycle(cycle)));
+            }
+
+            path.push(name);
+            for dependency in graph.nodes[name].config.dependencies.keys() {
+                visit(graph, dependency, path, order)?;
+            }
+            path.pop();
+
+            order.push(name.to_string());
+
+            Ok(())
+        }
+
+        let mut order = Vec::with_capacity(self.nodes.len());
+
+        for name in self.nodes.keys() {
+            visit(self, name, &mut Vec::new(), &mut order)?;
+        }
+
+        Ok(order)
+    }
+
+    /// Get a package in the graph
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Node> {
+        self.nodes.get(name)
+    }
+
+    /// Get every package in the graph with its smith, sorted so that every package comes after its dependencies
+    #[must_use]
+    pub fn packages(&self) -> Vec<WithSmith<'_>> {
+        self.order
+            .iter()
+            .map(|name| {
+                let node = &self.nodes[name];
+
+                WithSmith {
+                    smith: node.smith.clone(),
+                    package: Package {
+                        name,
+                        source: &node.source,
+                        config_package: &node.config,
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::smith::Git;
+    use std::path::Path;
+
+    fn graph(json: &str) -> Result<DependencyGraph, GraphError> {
+        let config = Config::from_json(json, Path::new("packages.json")).unwrap();
+
+        DependencyGraph::new(
+            &config,
+            &[Loaders::Git(Git::new())],
+            &ConditionContext {
+                os: "linux".to_string(),
+                arch: "x86_64".to_string(),
+                hostname: None,
+                neovim_version: None,
+            },
+        )
+    }
+
+    #[test]
+    fn test_deduplicate() {
+        let graph = graph(
+            r#"{ "packages": {
+                "github:nvim-telescope/telescope.nvim": {
+                    "dependencies": { "github:nvim-lua/plenary.nvim": {} }
+                },
+                "github:lewis6991/gitsigns.nvim": {
+                    "dependencies": { "github:nvim-lua/plenary.nvim": { "version": "tag:v0.1.3" } }
+                },
+                "github:nvim-lua/plenary.nvim": { "optional": true }
+            } }"#,
+        )
+        .unwrap();
+
+        let packages = graph.packages();
+        assert_eq!(packages.len(), 3);
+        assert_eq!(packages[0].package.name, "github:nvim-lua/plenary.nvim");
+
+        let plenary = graph.get("github:nvim-lua/plenary.nvim").unwrap();
+        assert_eq!(plenary.requested_by.len(), 3);
+        assert_eq!(plenary.config.version.as_deref(), Some("tag:v0.1.3"));
+        assert_eq!(plenary.config.optional, None);
+    }
+
+    #[test]
+    fn test_conflict() {
+        let report = graph(
+            r#"{ "packages": {
+                "github:nvim-telescope/telescope.nvim": {
+                    "dependencies": { "github:nvim-lua/plenary.nvim": { "version": "tag:v0.1.3" } }
+                },
+                "github:nvim-lua/plenary.nvim": { "version": "branch:master" }
+            } }"#,
+        )
+        .unwrap_err();
+
+        let conflict = report.downcast_ref::<Conflict>().unwrap();
+        assert_eq!(conflict.field, "version");
+        assert_eq!(
+            conflict.requests,
+            vec![
+                (Requester::Config, "branch:master".to_string()),
+                (
+                    Requester::Package("github:nvim-telescope/telescope.nvim".to_string()),
+                    "tag:v0.1.3".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_override() {
+        let graph = graph(
+            r#"{
+                "packages": {
+                    "github:nvim-telescope/telescope.nvim": {
+                        "dependencies": { "github:nvim-lua/plenary.nvim": { "version": "tag:v0.1.3" } }
+                    },
+                    "github:nvim-lua/plenary.nvim": { "version": "branch:master" }
+                },
+                "overrides": {
+                    "github:nvim-lua/plenary.nvim": { "source": "path:/src/plenary.nvim", "version": "branch:fix" }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let plenary = graph.get("github:nvim-lua/plenary.nvim").unwrap();
+        assert_eq!(plenary.source, "path:/src/plenary.nvim");
+        assert_eq!(plenary.config.version.as_deref(), Some("branch:fix"));
+    }
+
+    #[test]
+    fn test_cycle() {
+        let report = graph(
+            r#"{ "packages": {
+                "github:a/a.nvim": {
+                    "dependencies": { "github:b/b.nvim": { "dependencies": { "github:a/a.nvim": {} } } }
+                }
+            } }"#,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            report.downcast_ref::<Cycle>().unwrap().0,
+            ["github:a/a.nvim", "github:b/b.nvim", "github:a/a.nvim"]
+        );
+    }
+}
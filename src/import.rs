@@ -0,0 +1,490 @@
+//! A module which imports configs from other Neovim package managers
+//!
+//! lazy.nvim lockfiles, packer.nvim snapshots and vim-plug snapshots or `Plug` lines are read into a list of
+//! [`LockedPlugin`]s, which are then resolved into [`ImportedPackage`]s pinned to the commits that were recorded.
+//! Lockfiles which only record the plugin directory are resolved against the local checkouts of the plugins,
+//! so importing never needs network access.
+
+use crate::{
+    config::Config,
+    manifest::{Plugin, Resolution},
+    package::Config as PackageConfig,
+    smith::{enums::Inputs, Git, GitInput, Smith},
+};
+use error_stack::{Context, IntoReport, Report, Result, ResultExt};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A package manager to import from
+pub enum ImportFormat {
+    /// A lazy.nvim `lazy-lock.json` lockfile
+    LazyLock,
+    /// A packer.nvim snapshot, created with `:PackerSnapshot`
+    PackerSnapshot,
+    /// A vim-plug snapshot, created with `:PlugSnapshot`, or a vimscript file with `Plug` lines
+    VimPlug,
+}
+
+#[derive(Debug)]
+/// An error that can occur when importing a config
+pub enum ImportError {
+    /// A file could not be parsed
+    Parse,
+    /// The repository of a plugin could not be found
+    MissingSource(String),
+    /// The full commit of a plugin could not be found
+    MissingCommit(String),
+    /// The repository of a plugin cannot be loaded by alpacka
+    UnknownSource(String),
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse => f.write_str("Failed to parse file to import"),
+            Self::MissingSource(plugin) => {
+                write!(f, "Could not find the repository of plugin {plugin}")
+            }
+            Self::MissingCommit(plugin) => {
+                write!(f, "Could not find the full commit of plugin {plugin}")
+            }
+            Self::UnknownSource(source) => {
+                write!(f, "Repository {source} cannot be loaded by alpacka")
+            }
+        }
+    }
+}
+
+impl Context for ImportError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A plugin as recorded by another package manager
+pub struct LockedPlugin {
+    /// The name of the plugin's directory
+    pub directory: String,
+    /// The recorded commit, which may be abbreviated
+    pub commit: Option<String>,
+    /// The repository, as a URL or a GitHub `owner/repo` shorthand, if it was recorded
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A plugin which has been resolved into an alpacka package
+pub struct ImportedPackage {
+    /// The package name, e.g. `github:nvim-lua/plenary.nvim`
+    pub name: String,
+    /// The name of the plugin's directory, if it is different from the one alpacka would use
+    pub rename: Option<String>,
+    /// The full commit hash
+    pub commit: String,
+}
+
+#[derive(Deserialize)]
+/// An entry of a lazy.nvim lockfile or packer.nvim snapshot
+struct LockEntry {
+    commit: String,
+}
+
+impl ImportFormat {
+    /// Get the directories the package manager installs plugins to by default
+    ///
+    /// `nvim_data_path` is Neovim's data directory, usually `~/.local/share/nvim`
+    #[must_use]
+    pub fn default_plugin_dirs(self, nvim_data_path: &Path) -> Vec<PathBuf> {
+        match self {
+            Self::LazyLock => vec![nvim_data_path.join("lazy")],
+            Self::PackerSnapshot => {
+                let packer = nvim_data_path.join("site/pack/packer");
+                vec![packer.join("start"), packer.join("opt")]
+            }
+            Self::VimPlug => vec![nvim_data_path.join("plugged")],
+        }
+    }
+
+    /// Parse the contents of one or more files into a list of plugins
+    ///
+    /// vim-plug files are read together, so snapshot commits are matched with the `Plug` lines of another file
+    ///
+    /// # Errors
+    /// This function will return an error if a file cannot be parsed
+    pub fn parse(self, sources: &[String]) -> Result<Vec<LockedPlugin>, ImportError> {
+        match self {
+            Self::LazyLock | Self::PackerSnapshot => {
+                let mut plugins = Vec::new();
+
+                for source in sources {
+                    plugins.extend(parse_lock_file(source)?);
+                }
+
+                Ok(plugins)
+            }
+            Self::VimPlug => Ok(parse_vim_plug(&sources.join("\n"))),
+        }
+    }
+}
+
+/// Parse a lazy.nvim lockfile or a packer.nvim snapshot
+///
+/// Both formats map plugin directory names to objects with a `commit` field
+///
+/// # Errors
+/// This function will return an error if the file is not in that format
+pub fn parse_lock_file(source: &str) -> Result<Vec<LockedPlugin>, ImportError> {
+    let entries: BTreeMap<String, LockEntry> = serde_json::from_str(source)
+        .into_report()
+        .change_context(ImportError::Parse)?;
+
+    Ok(entries
+        .into_iter()
+        .map(|(directory, entry)| LockedPlugin {
+            directory,
+            commit: Some(entry.commit),
+            source: None,
+        })
+        .collect())
+}
+
+/// Parse `Plug` lines and vim-plug snapshot lines
+///
+/// `Plug 'owner/repo', { 'as': 'name', 'commit': 'sha' }` declares a plugin, and
+/// `silent! let g:plugs['name'].commit = 'sha'` pins a plugin declared elsewhere. Other lines are ignored.
+#[must_use]
+pub fn parse_vim_plug(source: &str) -> Vec<LockedPlugin> {
+    let mut plugins: Vec<LockedPlugin> = Vec::new();
+
+    for line in source.lines().map(str::trim) {
+        if let Some(arguments) = line.strip_prefix("Plug ") {
+            let Some(source) = quoted_strings(arguments).next() else {
+                continue;
+            };
+
+            let directory = vim_option(arguments, "as").unwrap_or_else(|| {
+                source
+                    .trim_end_matches('/')
+                    .trim_end_matches(".git")
+                    .rsplit(['/', ':'])
+                    .next()
+                    .unwrap_or(source)
+            });
+
+            plugins.push(LockedPlugin {
+                directory: directory.to_string(),
+                commit: vim_option(arguments, "commit").map(str::to_string),
+                source: Some(source.to_string()),
+            });
+        } else if let Some(assignment) = line
+            .trim_start_matches("silent!")
+            .trim_start()
+            .strip_prefix("let g:plugs[")
+        {
+            let mut strings = quoted_strings(assignment);
+            let (Some(directory), Some(commit)) = (strings.next(), strings.next()) else {
+                continue;
+            };
+
+            match plugins
+                .iter_mut()
+                .find(|plugin| plugin.directory == directory)
+            {
+                Some(plugin) => plugin.commit = Some(commit.to_string()),
+                None => plugins.push(LockedPlugin {
+                    directory: directory.to_string(),
+                    commit: Some(commit.to_string()),
+                    source: None,
+                }),
+            }
+        }
+    }
+
+    plugins
+}
+
+/// Iterate over the contents of the single or double quoted strings in a line of vimscript
+fn quoted_strings(line: &str) -> impl Iterator<Item = &str> {
+    let mut rest = line;
+
+    std::iter::from_fn(move || {
+        let start = rest.find(['\'', '"'])?;
+        let quote = rest[start..].chars().next()?;
+        let end = rest[start + 1..].find(quote)? + start + 1;
+        let string = &rest[start + 1..end];
+
+        rest = &rest[end + 1..];
+
+        Some(string)
+    })
+}
+
+/// Get the value of a string option in a vim-plug options dictionary
+fn vim_option<'a>(arguments: &'a str, key: &str) -> Option<&'a str> {
+    let mut strings = quoted_strings(arguments).skip(1);
+
+    while let Some(string) = strings.next() {
+        if string == key {
+            return strings.next();
+        }
+    }
+
+    None
+}
+
+/// Convert a repository URL, or a GitHub `owner/repo` shorthand, to an alpacka package name
+#[must_use]
+pub fn package_name_from_source(source: &str) -> Option<String> {
+    let source = source.trim_end_matches('/').trim_end_matches(".git");
+
+    let (host, path) = match source.split_once("://") {
+        Some((_, rest)) => {
+            let rest = rest.split_once('@').map_or(rest, |(_, rest)| rest);
+            rest.split_once('/')?
+        }
+        None => match source.split_once('@') {
+            Some((_, rest)) => rest.split_once(':')?,
+            None if !source.contains(':') && source.matches('/').count() == 1 => {
+                ("github.com", source)
+            }
+            None => return None,
+        },
+    };
+
+    if path.is_empty() || !path.contains('/') {
+        return None;
+    }
+
+    Some(match host {
+        "github.com" => format!("github:{path}"),
+        "gitlab.com" => format!("gitlab:{path}"),
+        "git.sr.ht" => format!("srht:{path}"),
+        host => format!("git:{host}:{path}"),
+    })
+}
+
+/// Resolve locked plugins into alpacka packages
+///
+/// Plugins without a recorded repository, or with an abbreviated commit, are looked up in their local checkout
+/// in one of `plugin_dirs`.
+///
+/// # Errors
+/// This function will return an error if the repository or full commit of a plugin cannot be found, or if alpacka
+/// cannot load its repository
+pub fn resolve_plugins(
+    plugins: Vec<LockedPlugin>,
+    plugin_dirs: &[PathBuf],
+    git: &Git,
+) -> Result<Vec<ImportedPackage>, ImportError> {
+    plugins
+        .into_iter()
+        .map(|plugin| {
+            let checkout = plugin_dirs
+                .iter()
+                .map(|dir| dir.join(&plugin.directory))
+                .find_map(|path| git2::Repository::open(path).ok());
+
+            let source = match (plugin.source, &checkout) {
+                (Some(source), _) => source,
+                (None, Some(repo)) => repo
+                    .find_remote("origin")
+                    .ok()
+                    .and_then(|remote| remote.url().map(str::to_string))
+                    .ok_or_else(|| {
+                        Report::new(ImportError::MissingSource(plugin.directory.clone()))
+                    })?,
+                (None, None) => {
+                    return Err(Report::new(ImportError::MissingSource(plugin.directory))
+                        .attach_printable("No local checkout was found in the plugin directories"))
+                }
+            };
+
+            let commit = match (plugin.commit, &checkout) {
+                (Some(commit), _) if is_full_commit(&commit) => commit,
+                (commit, Some(repo)) => repo
+                    .revparse_single(commit.as_deref().unwrap_or("HEAD"))
+                    .and_then(|object| object.peel_to_commit())
+                    .into_report()
+                    .change_context_lazy(|| ImportError::MissingCommit(plugin.directory.clone()))?
+                    .id()
+                    .to_string(),
+                (_, None) => {
+                    return Err(Report::new(ImportError::MissingCommit(plugin.directory))
+                        .attach_printable("No local checkout was found in the plugin directories"))
+                }
+            };
+
+            let name = package_name_from_source(&source)
+                .filter(|name| git.get_package_name(name).is_some())
+                .ok_or_else(|| Report::new(ImportError::UnknownSource(source.clone())))?;
+
+            let rename = (git.get_package_name(&name).as_ref() != Some(&plugin.directory))
+                .then_some(plugin.directory);
+
+            Ok(ImportedPackage {
+                name,
+                rename,
+                commit,
+            })
+        })
+        .collect()
+}
+
+/// Check if a commit is a full SHA-1 hash
+fn is_full_commit(commit: &str) -> bool {
+    commit.len() == 40 && commit.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Create a config which pins every imported package to its commit
+#[must_use]
+pub fn to_config(packages: &[ImportedPackage]) -> Config {
+    Config {
+        packages: packages
+            .iter()
+            .map(|package| {
+                (
+                    package.name.clone(),
+                    PackageConfig {
+                        version: Some(format!("commit:{}", package.commit)),
+                        rename: package.rename.clone(),
+                        ..PackageConfig::default()
+                    },
+                )
+            })
+            .collect(),
+        ..Config::default()
+    }
+}
+
+/// Create the manifest plugins for imported packages, without resolving them over the network
+///
+/// # Errors
+/// This function will return an error if the remote of a package cannot be found
+pub fn to_plugins(packages: &[ImportedPackage], git: &Git) -> Result<Vec<Plugin>, ImportError> {
+    packages
+        .iter()
+        .map(|package| {
+            let remote = git
+                .remote_url(&package.name)
+                .change_context_lazy(|| ImportError::UnknownSource(package.name.clone()))?;
+
+            Ok(Plugin {
+                name: git
+                    .get_package_name(&package.name)
+                    .ok_or_else(|| Report::new(ImportError::UnknownSource(package.name.clone())))?,
+                unresolved_name: package.name.clone(),
+                rename: package.rename.clone(),
+                optional: false,
+                dependencies: Vec::new(),
+                smith: git.name(),
+                build: String::new(),
+                loader_data: Inputs::Git(GitInput::new(package.commit.clone(), remote)),
+                overridden: None,
+                patches: Vec::new(),
+                // the same as `to_config` declares, so `alpacka install` reuses these inputs instead of resolving them
+                resolved_from: Some(Resolution {
+                    source: package.name.clone(),
+                    version: Some(format!("commit:{}", package.commit)),
+                }),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMIT: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    #[test]
+    fn test_package_name_from_source() {
+        for (source, name) in [
+            ("nvim-lua/plenary.nvim", "github:nvim-lua/plenary.nvim"),
+            (
+                "https://github.com/nvim-lua/plenary.nvim.git",
+                "github:nvim-lua/plenary.nvim",
+            ),
+            ("git@gitlab.com:foo/bar.git", "gitlab:foo/bar"),
+            ("https://git.sr.ht/~foo/bar", "srht:~foo/bar"),
+            ("https://codeberg.org/foo/bar", "git:codeberg.org:foo/bar"),
+        ] {
+            assert_eq!(package_name_from_source(source).as_deref(), Some(name));
+        }
+
+        assert_eq!(package_name_from_source("plenary.nvim"), None);
+    }
+
+    #[test]
+    fn test_parse_vim_plug() {
+        let plugins = parse_vim_plug(&format!(
+            r#"
+            " plugins
+            Plug 'tpope/vim-fugitive'
+            Plug 'catppuccin/nvim', {{ 'as': 'catppuccin' }}
+            silent! let g:plugs['vim-fugitive'].commit = '{COMMIT}'
+            silent! let g:plugs['catppuccin'].commit = 'abc1234'
+            "#
+        ));
+
+        assert_eq!(
+            plugins,
+            [
+                LockedPlugin {
+                    directory: "vim-fugitive".to_string(),
+                    commit: Some(COMMIT.to_string()),
+                    source: Some("tpope/vim-fugitive".to_string()),
+                },
+                LockedPlugin {
+                    directory: "catppuccin".to_string(),
+                    commit: Some("abc1234".to_string()),
+                    source: Some("catppuccin/nvim".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_plugins() {
+        let plugins = vec![LockedPlugin {
+            directory: "catppuccin".to_string(),
+            commit: Some(COMMIT.to_string()),
+            source: Some("catppuccin/nvim".to_string()),
+        }];
+
+        let packages = resolve_plugins(plugins, &[], &Git::new()).unwrap();
+
+        assert_eq!(
+            packages,
+            [ImportedPackage {
+                name: "github:catppuccin/nvim".to_string(),
+                rename: Some("catppuccin".to_string()),
+                commit: COMMIT.to_string(),
+            }]
+        );
+
+        let config = to_config(&packages);
+        assert_eq!(
+            config.packages["github:catppuccin/nvim"].version.as_deref(),
+            Some(format!("commit:{COMMIT}").as_str())
+        );
+
+        // the plugins are resolved from what the config declares, so installing the config keeps their inputs
+        let plugins = to_plugins(&packages, &Git::new()).unwrap();
+        assert_eq!(
+            plugins[0].resolved_from,
+            Some(Resolution {
+                source: "github:catppuccin/nvim".to_string(),
+                version: config.packages["github:catppuccin/nvim"].version.clone(),
+            })
+        );
+
+        // lockfiles without a local checkout cannot be resolved
+        let locked = parse_lock_file(&format!(
+            r#"{{ "plenary.nvim": {{ "branch": "master", "commit": "{COMMIT}" }} }}"#
+        ))
+        .unwrap();
+        assert!(resolve_plugins(locked, &[], &Git::new()).is_err());
+    }
+}
@@ -10,7 +10,18 @@
 //! It also exports functions to run the package manager, such as resolving and loading plugins.
 //!
 //! This is NOT meant to be used by end-users, but rather by other programs that want to use alpacka as a library, such as a user-facing GUI/neovim plugin.
+pub mod breaking;
+pub mod changelog;
+pub mod condition;
 pub mod config;
+pub mod diff;
+pub mod export;
+pub mod graph;
+pub mod import;
 pub mod manifest;
+pub mod outdated;
 pub mod package;
+pub mod plan;
+pub mod rollback;
 pub mod smith;
+pub mod update;
@@ -0,0 +1,53 @@
+//! Plugins, manifests and git repositories shared by the tests of modules which compare manifests
+
+use super::{Manifest, Plugin};
+use crate::smith::{enums::Inputs, GitInput};
+use std::path::Path;
+
+/// Create a git plugin from `nvim-lua` at a commit
+pub fn plugin(name: &str, commit: &str) -> Plugin {
+    Plugin {
+        name: name.to_string(),
+        unresolved_name: format!("github:nvim-lua/{name}"),
+        rename: None,
+        optional: false,
+        dependencies: vec![],
+        smith: "git".to_string(),
+        build: String::new(),
+        loader_data: Inputs::Git(GitInput::new(
+            commit.to_string(),
+            format!("https://github.com/nvim-lua/{name}.git"),
+        )),
+        overridden: None,
+        patches: Vec::new(),
+        resolved_from: None,
+    }
+}
+
+/// Create a manifest of plugins, without any conditions
+pub fn manifest(plugins: Vec<Plugin>) -> Manifest {
+    Manifest::new("0.9.0".to_string(), plugins, vec![])
+}
+
+/// Commit `contents` as `init.lua` to the head of a repository, with the contents as the message
+pub fn commit(repo: &git2::Repository, contents: &str) -> git2::Oid {
+    std::fs::write(repo.workdir().unwrap().join("init.lua"), contents).unwrap();
+
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("init.lua")).unwrap();
+    index.write().unwrap();
+
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("alpacka", "alpacka@example.com").unwrap();
+    let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        contents,
+        &tree,
+        parent.as_slice().iter().collect::<Vec<_>>().as_slice(),
+    )
+    .unwrap()
+}
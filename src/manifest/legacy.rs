@@ -0,0 +1,127 @@
+//! The generations file format used before generations files had a header
+//!
+//! Generations were keyed by a [`std::collections::hash_map::DefaultHasher`] hash of the config, and manifests did
+//! not record condition outcomes. This format is only read, to migrate old files.
+
+use super::{ConfigHash, GenerationsFileError};
+use crate::smith::enums::Inputs;
+use bytecheck::CheckBytes;
+use error_stack::{Report, Result};
+use rkyv::{check_archived_root, Archive, Deserialize, Infallible};
+use std::collections::BTreeMap;
+
+#[derive(Archive, rkyv::Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[archive_attr(derive(CheckBytes, Eq, PartialEq, PartialOrd, Ord))]
+struct GenerationHash(u64, u64);
+
+#[derive(Archive, rkyv::Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes))]
+pub(super) struct Plugin {
+    name: String,
+    unresolved_name: String,
+    rename: Option<String>,
+    optional: bool,
+    dependencies: Vec<String>,
+    smith: String,
+    build: String,
+    loader_data: Inputs,
+}
+
+#[derive(Archive, rkyv::Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes))]
+struct Manifest {
+    neovim_version: String,
+    plugins: Vec<Plugin>,
+}
+
+#[derive(Archive, rkyv::Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes))]
+struct GenerationsFile(BTreeMap<GenerationHash, Manifest>);
+
+impl Plugin {
+    pub(super) fn migrate(self) -> super::Plugin {
+        super::Plugin {
+            name: self.name,
+            unresolved_name: self.unresolved_name,
+            rename: self.rename,
+            optional: self.optional,
+            dependencies: self.dependencies,
+            smith: self.smith,
+            build: self.build,
+            loader_data: self.loader_data,
+            overridden: None,
+            patches: Vec::new(),
+            resolved_from: None,
+        }
+    }
+}
+
+/// Migrate a generations file written before the header was added
+///
+/// Every generation keeps its old hash as a [`ConfigHash::Legacy`], as the configs they were created from are not known
+///
+/// # Panics
+/// Cannot panic, as the only error that can occur when deserializing is [`Infallible`]
+pub(super) fn migrate(bytes: &[u8]) -> Result<super::GenerationsFile, GenerationsFileError> {
+    let archived = check_archived_root::<GenerationsFile>(bytes).map_err(|error| {
+        Report::new(GenerationsFileError::Parse).attach_printable(error.to_string())
+    })?;
+
+    let GenerationsFile(generations) = archived.deserialize(&mut Infallible).unwrap();
+
+    Ok(super::GenerationsFile {
+        // the creation order of old generations is unknown, so they get IDs in the order they are stored in
+        generations: generations
+            .into_iter()
+            .zip(1..)
+            .map(|((GenerationHash(hash, generation), manifest), id)| {
+                (
+                    super::GenerationHash(ConfigHash::Legacy(hash), generation),
+                    super::Generation {
+                        id,
+                        created_at: 0,
+                        metadata: super::Metadata::default(),
+                        config: None,
+                        manifest: super::Manifest {
+                            neovim_version: manifest.neovim_version,
+                            plugins: manifest.plugins.into_iter().map(Plugin::migrate).collect(),
+                            conditions: Vec::new(),
+                        },
+                    },
+                )
+            })
+            .collect(),
+        active: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rkyv::to_bytes;
+
+    #[test]
+    fn test_migrate() {
+        let legacy = GenerationsFile(BTreeMap::from([(
+            GenerationHash(42, 1),
+            Manifest {
+                neovim_version: "0.9.0".to_string(),
+                plugins: vec![],
+            },
+        )]));
+        let bytes = to_bytes::<_, 1024>(&legacy).unwrap();
+
+        let mut generations = super::super::GenerationsFile::from_bytes(&bytes).unwrap();
+        assert!(generations
+            .generations
+            .contains_key(&super::super::GenerationHash(ConfigHash::Legacy(42), 1)));
+
+        let hash = ConfigHash::Sha256([0; 32]);
+        assert!(generations.adopt_legacy(42, hash));
+        assert_eq!(generations.get_latest_generation_number(hash), Some(1));
+
+        // adopted generations keep their ID
+        let generation = &generations.generations[&super::super::GenerationHash(hash, 1)];
+        assert_eq!((generation.id, generation.created_at), (1, 0));
+    }
+}
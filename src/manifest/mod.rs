@@ -0,0 +1,1075 @@
+//! A module which contains the manifest and generations file formats
+//!
+//! A generations file starts with a 16 byte header: the magic bytes `ALPACKA\0`, the format version as a little endian
+//! `u32`, and 4 bytes of padding, which keep the archive after the header aligned. Files written before the header was
+//! added, or with an older format version, are migrated with [`GenerationsFile::from_bytes`].
+
+#[cfg(test)]
+pub(crate) mod fixtures;
+mod legacy;
+mod v1;
+
+use bytecheck::CheckBytes;
+use error_stack::{Context, IntoReport, Report, Result, ResultExt};
+use rkyv::{check_archived_root, to_bytes, Archive, Deserialize as RkyvDeserialize, Infallible};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Write as FmtWrite},
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    condition::ConditionOutcome,
+    package::{Config as ConfigPackage, Override, Package, WithSmith},
+    smith::{
+        enums::{Inputs, Loaders},
+        LoadError,
+    },
+};
+
+/// The bytes every generations file starts with
+const MAGIC: &[u8; 8] = b"ALPACKA\0";
+/// The length of the header, which is a multiple of 16 so the archive after it stays aligned
+const HEADER_LENGTH: usize = 16;
+/// The version of the generations file format written by this version of alpacka
+///
+/// Every change to the archived layout of [`GenerationsFile`] bumps it, and adds a module which migrates the previous
+/// version to [`GenerationsFile::from_bytes`]
+pub const FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug)]
+/// An error that can occur when reading or writing a generations file
+pub enum GenerationsFileError {
+    /// The generations file could not be read
+    Read,
+    /// The generations file could not be written
+    Write,
+    /// The generations file is not valid
+    Parse,
+    /// The generations file was written by an older alpacka, without a header or with an older format version, and
+    /// has to be migrated with [`GenerationsFile::from_bytes`]
+    Legacy,
+    /// The generations file was written by a newer alpacka
+    UnsupportedVersion(u32),
+    /// The generations could not be serialized
+    Serialize,
+}
+
+impl Display for GenerationsFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read => f.write_str("Failed to read generations file"),
+            Self::Write => f.write_str("Failed to write generations file"),
+            Self::Parse => f.write_str("Failed to parse generations file"),
+            Self::Legacy => {
+                f.write_str("Generations file was written by an older alpacka and has to be migrated")
+            }
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "Generations file has format version {version}, but this alpacka only supports version {FORMAT_VERSION}"
+            ),
+            Self::Serialize => f.write_str("Failed to serialize generations file"),
+        }
+    }
+}
+
+impl Context for GenerationsFileError {}
+
+#[derive(
+    Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Clone,
+    Copy,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+#[archive(compare(PartialEq))]
+#[archive_attr(derive(CheckBytes, Eq, PartialEq, PartialOrd, Ord))]
+/// The hash of the config a generation was created from
+///
+/// See [`Config::stable_hash`](crate::config::Config::stable_hash) for how configs are hashed
+pub enum ConfigHash {
+    /// A hash from an older alpacka, which could not be matched to a config when the generations file was migrated
+    Legacy(u64),
+    /// The SHA-256 of the canonical serialization of a config
+    Sha256([u8; 32]),
+}
+
+impl Display for ConfigHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Legacy(hash) => write!(f, "legacy:{hash}"),
+            Self::Sha256(hash) => {
+                f.write_str("sha256:")?;
+
+                for byte in hash {
+                    write!(f, "{byte:02x}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(
+    Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+)]
+#[archive_attr(derive(CheckBytes, Eq, PartialEq, PartialOrd, Ord))]
+/// A hash of the config file
+///
+/// The first value is the hash of the config file
+/// The second value is the generation number
+pub struct GenerationHash(pub ConfigHash, pub u64);
+
+/// A generation, as stored in the generations file
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug)]
+#[archive_attr(derive(CheckBytes))]
+pub struct Generation {
+    /// The ID of the generation, which is unique across every config and increases with every new generation
+    pub id: u64,
+    /// When the generation was created, in seconds since the Unix epoch
+    /// This is 0 for generations migrated from an older alpacka, as they did not record it
+    pub created_at: i64,
+    /// Where and why the generation was created
+    pub metadata: Metadata,
+    /// The config the generation was created from
+    /// This is empty for generations migrated from an older alpacka, as they did not record it
+    pub config: Option<ConfigSnapshot>,
+    /// The manifest of the generation
+    pub manifest: Manifest,
+}
+
+/// The config a generation was created from
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[archive_attr(derive(CheckBytes))]
+pub struct ConfigSnapshot {
+    /// The config with its files merged, before a profile was selected, see
+    /// [`Config::snapshot`](crate::config::Config::snapshot)
+    pub merged: String,
+    /// The files the config was loaded from, as they were written, see
+    /// [`Config::load_with_sources`](crate::config::Config::load_with_sources)
+    pub sources: Vec<ConfigSource>,
+}
+
+/// A file a config was loaded from
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[archive_attr(derive(CheckBytes))]
+pub struct ConfigSource {
+    /// The path of the file, relative to the directory of the config file, or absolute if it is outside of it
+    pub path: String,
+    /// The contents of the file
+    pub contents: String,
+}
+
+/// Where and why a generation was created
+///
+/// Every field is empty for generations migrated from an older alpacka, as they did not record it
+#[derive(
+    Archive, rkyv::Serialize, rkyv::Deserialize, Debug, Clone, Default, Serialize, Deserialize,
+)]
+#[archive_attr(derive(CheckBytes))]
+pub struct Metadata {
+    /// A description of the generation, given by the user
+    pub description: Option<String>,
+    /// The version of alpacka which created the generation
+    pub alpacka_version: Option<String>,
+    /// The hostname of the machine which created the generation, if it could be found
+    pub hostname: Option<String>,
+    /// The path to the config file the generation was created from
+    pub config_path: Option<String>,
+    /// How long resolving the packages of the generation took, in milliseconds
+    pub resolution_duration_ms: Option<u64>,
+}
+
+impl Metadata {
+    /// Get the metadata of a generation created now, on this machine, from a config file
+    ///
+    /// The description and resolution duration are left empty
+    #[must_use]
+    pub fn detect(config_path: &Path) -> Self {
+        Self {
+            description: None,
+            alpacka_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            hostname: hostname::get()
+                .ok()
+                .and_then(|hostname| hostname.into_string().ok()),
+            config_path: Some(
+                std::fs::canonicalize(config_path)
+                    .unwrap_or_else(|_| config_path.to_path_buf())
+                    .display()
+                    .to_string(),
+            ),
+            resolution_duration_ms: None,
+        }
+    }
+}
+
+/// A file which contains a list of all the generations
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Debug)]
+#[archive_attr(derive(CheckBytes))]
+pub struct GenerationsFile {
+    /// Every generation, keyed by the config hash and the generation number
+    pub generations: BTreeMap<GenerationHash, Generation>,
+    /// The generation which is installed, if any
+    pub active: Option<GenerationHash>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct JsonGenerationsFile(pub BTreeMap<String, Json>);
+
+/// Get the format version of a generations file, or [`Option::None`] if it was written before the header was added
+#[must_use]
+pub fn format_version(bytes: &[u8]) -> Option<u32> {
+    if !bytes.starts_with(MAGIC) {
+        return None;
+    }
+
+    bytes
+        .get(MAGIC.len()..MAGIC.len() + 4)
+        .and_then(|version| version.try_into().ok())
+        .map(u32::from_le_bytes)
+}
+
+/// Get the generations archived in a generations file, without copying them
+///
+/// # Errors
+/// This function will return an error if the file is not valid, or if it does not have the current format version
+pub fn archived_generations(
+    bytes: &[u8],
+) -> Result<&ArchivedGenerationsFile, GenerationsFileError> {
+    match format_version(bytes) {
+        Some(FORMAT_VERSION) => check_archived_root::<GenerationsFile>(&bytes[HEADER_LENGTH..])
+            .map_err(|error| {
+                Report::new(GenerationsFileError::Parse).attach_printable(error.to_string())
+            }),
+        Some(version) if version > FORMAT_VERSION => Err(Report::new(
+            GenerationsFileError::UnsupportedVersion(version),
+        )),
+        Some(_) | None => Err(Report::new(GenerationsFileError::Legacy)),
+    }
+}
+
+impl GenerationsFile {
+    /// Create a new generations file
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            generations: BTreeMap::new(),
+            active: None,
+        }
+    }
+
+    /// Read a generations file in the current format, or migrate one written by an older alpacka
+    ///
+    /// Generations in old files keep their old hash as a [`ConfigHash::Legacy`], see [`GenerationsFile::adopt_legacy`]
+    ///
+    /// # Errors
+    /// This function will return an error if the file is not valid, or if it was written by a newer alpacka
+    ///
+    /// # Panics
+    /// Cannot panic, as the only error that can occur when deserializing is [`Infallible`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, GenerationsFileError> {
+        match format_version(bytes) {
+            None => legacy::migrate(bytes),
+            Some(1) => v1::migrate(&bytes[HEADER_LENGTH..]),
+            Some(_) => Ok(archived_generations(bytes)?
+                .deserialize(&mut Infallible)
+                .unwrap()),
+        }
+    }
+
+    /// Serialize the generations file, including its header
+    ///
+    /// # Errors
+    /// This function will return an error if the generations can't be serialized
+    pub fn to_bytes(&self) -> Result<Vec<u8>, GenerationsFileError> {
+        let archive = to_bytes::<_, 1024>(self).map_err(|error| {
+            Report::new(GenerationsFileError::Serialize).attach_printable(error.to_string())
+        })?;
+
+        let mut bytes = Vec::with_capacity(HEADER_LENGTH + archive.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.resize(HEADER_LENGTH, 0);
+        bytes.extend_from_slice(&archive);
+
+        Ok(bytes)
+    }
+
+    /// Save the generations file to a file
+    ///
+    /// # Errors
+    /// This function will return an error if the file can't be created, or if the generations can't be serialized
+    pub fn save_to_file(
+        &self,
+        generation_path: &PathBuf,
+    ) -> std::result::Result<(), std::io::Error> {
+        let file = File::create(generation_path)?;
+
+        let bytes = self
+            .to_bytes()
+            .map_err(|e| io::Error::other(e.to_string()))?;
+
+        let mut writer = BufWriter::new(file);
+
+        writer.write_all(&bytes).map_err(io::Error::other)?;
+        Ok(())
+    }
+
+    /// Move the generations stored under the legacy hash of a config to its stable hash
+    ///
+    /// The generations are numbered after any generations which already have the stable hash.
+    /// Returns whether any generations were moved
+    pub fn adopt_legacy(&mut self, legacy_hash: u64, config_hash: ConfigHash) -> bool {
+        let legacy: Vec<_> = self
+            .generations
+            .keys()
+            .filter(|GenerationHash(hash, _)| *hash == ConfigHash::Legacy(legacy_hash))
+            .copied()
+            .collect();
+
+        for hash in &legacy {
+            if let Some(generation) = self.generations.remove(hash) {
+                self.insert(config_hash, generation);
+            }
+        }
+
+        !legacy.is_empty()
+    }
+
+    /// Add a new generation to the generations file, created now
+    ///
+    /// returns the [`GenerationHash`] of the new generation
+    pub fn add_to_generations(
+        &mut self,
+        config_hash: ConfigHash,
+        manifest: Manifest,
+        metadata: Metadata,
+        config: Option<ConfigSnapshot>,
+    ) -> GenerationHash {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| {
+                i64::try_from(duration.as_secs()).unwrap_or(i64::MAX)
+            });
+
+        let generation = Generation {
+            id: self.get_next_id(),
+            created_at,
+            metadata,
+            config,
+            manifest,
+        };
+
+        self.insert(config_hash, generation)
+    }
+
+    /// Insert a generation as the next generation of a config hash
+    fn insert(&mut self, config_hash: ConfigHash, generation: Generation) -> GenerationHash {
+        let generation_number = self.get_next_generation_number(config_hash);
+        let hash = GenerationHash(config_hash, generation_number);
+
+        self.generations.insert(hash, generation);
+
+        hash
+    }
+
+    /// Get the ID the next generation will have, which is higher than the ID of every generation of every config
+    #[must_use]
+    pub fn get_next_id(&self) -> u64 {
+        self.generations
+            .values()
+            .map(|generation| generation.id)
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+
+    /// Get a generation of any config by its ID
+    #[must_use]
+    pub fn get_by_id(&self, id: u64) -> Option<(&GenerationHash, &Generation)> {
+        self.generations
+            .iter()
+            .find(|(_, generation)| generation.id == id)
+    }
+
+    /// Get the generation of the same config before a generation
+    /// returns [`Option::None`] if it is the first generation of its config
+    #[must_use]
+    pub fn get_previous(&self, hash: GenerationHash) -> Option<GenerationHash> {
+        let GenerationHash(config_hash, generation) = hash;
+
+        self.generations
+            .keys()
+            .filter(|GenerationHash(hash, number)| *hash == config_hash && *number < generation)
+            .max_by_key(|GenerationHash(_, number)| *number)
+            .copied()
+    }
+
+    /// Get every generation of every config, in the order they were created
+    #[must_use]
+    pub fn timeline(&self) -> Vec<(&GenerationHash, &Generation)> {
+        let mut timeline: Vec<_> = self.generations.iter().collect();
+        timeline.sort_by_key(|(_, generation)| generation.id);
+
+        timeline
+    }
+
+    /// Get the latest manifest for a config hash
+    /// returns [`Option::None`] if there is no generation associated with that config hash
+    ///
+    /// Else returns the latest [Manifest] for that config hash
+    #[must_use]
+    pub fn get_latest_manifest(&self, config_hash: ConfigHash) -> Option<&Manifest> {
+        self.generations
+            .iter()
+            .filter(|(GenerationHash(hash, _), _)| *hash == config_hash)
+            .max_by_key(|(GenerationHash(_, generation), _)| *generation)
+            .map(|(_, generation)| &generation.manifest)
+    }
+
+    /// Get the latest generation number for a config hash
+    ///
+    /// returns [`Option::None`] if there is no generation associated with that config hash
+    /// else returns the latest generation number for that config hash
+    #[must_use]
+    pub fn get_latest_generation_number(&self, config_hash: ConfigHash) -> Option<u64> {
+        self.generations
+            .keys()
+            .filter(|GenerationHash(hash, _)| *hash == config_hash)
+            .max_by_key(|GenerationHash(_, generation)| generation)
+            .map(|GenerationHash(_, generation)| generation)
+            .copied()
+    }
+
+    /// Get the next generation number for a config hash
+    /// returns 1 if there is no manifest for that config hash
+    #[must_use]
+    pub fn get_next_generation_number(&self, config_hash: ConfigHash) -> u64 {
+        self.get_latest_generation_number(config_hash).unwrap_or(0) + 1
+    }
+}
+
+impl Default for GenerationsFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Get the latest manifest for a config hash
+///
+/// returns [`Option::None`] if the config doesn't have any associated generations
+/// else, returns the latest [`Manifest`] as a an [`ArchivedManifest`] for that config hash
+#[must_use]
+pub fn get_latest(
+    generation_file: &ArchivedGenerationsFile,
+    config_hash: ConfigHash,
+) -> Option<&ArchivedManifest> {
+    generation_file
+        .generations
+        .iter()
+        .filter(|(ArchivedGenerationHash(hash, _), _)| *hash == config_hash)
+        .max_by_key(|(ArchivedGenerationHash(_, generation), _)| *generation)
+        .map(|(_, generation)| &generation.manifest)
+}
+
+/// Get the manifest of a generation of a config hash
+///
+/// returns [`Option::None`] if the config hash doesn't have a generation with that number
+#[must_use]
+pub fn get_generation(
+    generation_file: &ArchivedGenerationsFile,
+    config_hash: ConfigHash,
+    generation: u64,
+) -> Option<&ArchivedManifest> {
+    generation_file
+        .generations
+        .iter()
+        .find(|(ArchivedGenerationHash(hash, number), _)| {
+            *hash == config_hash && *number == generation
+        })
+        .map(|(_, generation)| &generation.manifest)
+}
+
+/// Get the number of the active generation, if it belongs to a config hash
+///
+/// returns [`Option::None`] if no generation is active, or if the active generation belongs to another config
+#[must_use]
+pub fn get_active_generation(
+    generation_file: &ArchivedGenerationsFile,
+    config_hash: ConfigHash,
+) -> Option<u64> {
+    generation_file
+        .active
+        .as_ref()
+        .filter(|ArchivedGenerationHash(hash, _)| *hash == config_hash)
+        .map(|ArchivedGenerationHash(_, generation)| *generation)
+}
+
+/// Get the manifest of the active generation, which is the one installed, whichever config it belongs to
+#[must_use]
+pub fn get_installed(generation_file: &ArchivedGenerationsFile) -> Option<&ArchivedManifest> {
+    generation_file
+        .active
+        .as_ref()
+        .and_then(|active| generation_file.generations.get(active))
+        .map(|generation| &generation.manifest)
+}
+
+/// Get the generation of a config hash which should be installed, and its manifest
+///
+/// This is the active generation if it belongs to the config hash, so rollbacks are kept. Else it is the latest
+/// generation of the config hash
+#[must_use]
+pub fn get_current(
+    generation_file: &ArchivedGenerationsFile,
+    config_hash: ConfigHash,
+) -> Option<(u64, &ArchivedManifest)> {
+    get_active_generation(generation_file, config_hash)
+        .or_else(|| get_latest_generation_number(generation_file, config_hash))
+        .and_then(|generation| {
+            get_generation(generation_file, config_hash, generation)
+                .map(|manifest| (generation, manifest))
+        })
+}
+
+/// Get the manifest of the most recently created generation of any config, which new manifests reuse resolved
+/// packages from
+#[must_use]
+pub fn get_most_recent(generation_file: &ArchivedGenerationsFile) -> Option<&ArchivedManifest> {
+    generation_file
+        .generations
+        .values()
+        .max_by_key(|generation| generation.id)
+        .map(|generation| &generation.manifest)
+}
+
+/// Get the latest generation number for a config hash
+/// returns [`Option::None`] if the config doesn't have any associated generations
+///
+/// else, returns the latest generation number for that config hash
+#[must_use]
+pub fn get_latest_generation_number(
+    generation_file: &ArchivedGenerationsFile,
+    config_hash: ConfigHash,
+) -> Option<u64> {
+    generation_file
+        .generations
+        .keys()
+        .filter(|ArchivedGenerationHash(hash, _)| *hash == config_hash)
+        .max_by_key(|ArchivedGenerationHash(_, generation)| generation)
+        .map(|ArchivedGenerationHash(_, generation)| generation)
+        .copied()
+}
+
+/// Get the next generation number for a config hash
+/// returns 1 if the config hash doesn't have any associated generations
+#[must_use]
+pub fn get_next_generation_number(
+    generation_file: &ArchivedGenerationsFile,
+    config_hash: ConfigHash,
+) -> u64 {
+    get_latest_generation_number(generation_file, config_hash).unwrap_or(0) + 1
+}
+
+/// Add a new generation to the generations file
+///
+/// returns a deserialized [`GenerationFile`] with the new generation added
+///
+/// # Panics
+/// Cannot panic, as the only error that can occur is [`Infallible`]
+pub fn add_to_generations(
+    generation_file: &ArchivedGenerationsFile,
+    config_hash: ConfigHash,
+    manifest: Manifest,
+    metadata: Metadata,
+    config: Option<ConfigSnapshot>,
+) -> GenerationsFile {
+    let mut generations: GenerationsFile = generation_file.deserialize(&mut Infallible).unwrap();
+
+    generations.add_to_generations(config_hash, manifest, metadata, config);
+
+    generations
+}
+
+/// An alpacka manifest
+///
+/// This is the file that is generated by alpacka and is used to install plugins
+/// Contains a list of plugins and the neovim version it was built for
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+#[archive_attr(derive(CheckBytes))]
+pub struct Manifest {
+    /// The neovim version this manifest was built for
+    pub neovim_version: String,
+    pub plugins: Vec<Plugin>,
+    /// The outcomes of the package conditions which were evaluated when creating this manifest
+    pub conditions: Vec<ConditionOutcome>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Json {
+    /// The neovim version this manifest was built for
+    pub neovim_version: String,
+    pub plugins: Vec<Plugin>,
+    pub conditions: Vec<ConditionOutcome>,
+    // These need to be strings because u64 cannot fit into JSON
+    pub hash: String,
+    pub generation: String,
+    pub id: String,
+    pub created_at: String,
+    /// Whether this generation is the installed one
+    pub active: bool,
+    pub metadata: Metadata,
+}
+
+impl Manifest {
+    #[must_use]
+    pub const fn new(
+        neovim_version: String,
+        plugins: Vec<Plugin>,
+        conditions: Vec<ConditionOutcome>,
+    ) -> Self {
+        Self {
+            neovim_version,
+            plugins,
+            conditions,
+        }
+    }
+
+    /// Get the inputs this manifest resolved a package to, if the package is still resolved from the same source and
+    /// version by the same smith
+    ///
+    /// Packages which did not change since this manifest was created can reuse these inputs instead of being
+    /// resolved again
+    #[must_use]
+    pub fn reusable_inputs(&self, package: &WithSmith) -> Option<&Inputs> {
+        let resolution = Resolution::of(&package.package);
+
+        self.plugins
+            .iter()
+            .find(|plugin| {
+                plugin.unresolved_name == package.package.name
+                    && plugin.smith == package.smith
+                    && plugin.resolved_from.as_ref() == Some(&resolution)
+            })
+            .map(|plugin| &plugin.loader_data)
+    }
+
+    /// Save the manifest to a file
+    ///
+    /// # Errors
+    /// This function will return an error if the file can't be created, or if the manifest can't be serialized
+    #[tracing::instrument]
+    pub fn save_to_file(&self, path: &PathBuf) -> std::result::Result<(), std::io::Error> {
+        let file = std::fs::File::create(path)?;
+        let bytes = to_bytes::<_, 1024>(self).map_err(std::io::Error::other)?;
+        let mut writer = std::io::BufWriter::new(file);
+
+        writer.write_all(&bytes).map_err(std::io::Error::other)?;
+
+        Ok(())
+    }
+}
+
+/// A plugin, as defined in the manifest
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes))]
+pub struct Plugin {
+    /// The plugin's name
+    pub name: String,
+    /// The plugin's unresolved name
+    /// This is the name that is used in the config file
+    /// This is used to resolve dependencies
+    pub unresolved_name: String,
+    /// Rename the plugin to this name when loading
+    pub rename: Option<String>,
+    /// If the plugin is optional
+    pub optional: bool,
+    /// The plugin's dependencies, as a list of plugin names. These are the non-resolved names
+    pub dependencies: Vec<String>,
+    /// the name of the loader this plugin is loaded by
+    pub smith: String,
+    /// A command which is run in the plugin's directory after loading
+    pub build: String,
+    /// The data which is used for the loader
+    pub loader_data: Inputs,
+    /// The override which replaced the plugin's source or version, if there is one
+    pub overridden: Option<Override>,
+    /// The patches which are applied in order after the plugin is loaded
+    pub patches: Vec<Patch>,
+    /// What the plugin was resolved from. This is [`None`] for plugins which were not resolved from a config, such
+    /// as migrated ones, and these are always resolved again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_from: Option<Resolution>,
+}
+
+/// The inputs a plugin was resolved from
+///
+/// A package which still resolves from the same source and version does not have to be resolved again, and reuses
+/// the [`Inputs`] of the plugin instead
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    Serialize,
+    Deserialize,
+)]
+#[archive_attr(derive(CheckBytes, Debug))]
+pub struct Resolution {
+    /// The name the plugin was resolved from, which is the override source if there is one
+    pub source: String,
+    /// The version the plugin was resolved at, or [`None`] for the default branch
+    pub version: Option<String>,
+}
+
+impl Resolution {
+    /// Get what a package is resolved from
+    #[must_use]
+    pub fn of(package: &Package) -> Self {
+        Self {
+            source: package.source.to_string(),
+            version: package.config_package.version.clone(),
+        }
+    }
+
+    /// Get a package declaration which resolves from the same version, to resolve the plugin again
+    #[must_use]
+    pub fn to_config(&self) -> ConfigPackage {
+        ConfigPackage {
+            version: self.version.clone(),
+            ..ConfigPackage::default()
+        }
+    }
+}
+
+/// A patch file which is applied to a plugin
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    Serialize,
+    Deserialize,
+)]
+#[archive_attr(derive(CheckBytes, Debug))]
+pub struct Patch {
+    /// The absolute path of the patch file
+    pub path: String,
+    /// The SHA-256 of the patch file's contents, in hex
+    pub sha256: String,
+}
+
+impl Patch {
+    /// Hash the contents of a patch file
+    #[must_use]
+    pub fn digest(contents: &[u8]) -> String {
+        let mut hex = String::with_capacity(64);
+
+        for byte in Sha256::digest(contents) {
+            let _ = write!(hex, "{byte:02x}");
+        }
+
+        hex
+    }
+}
+
+impl Plugin {
+    /// Check if this plugin is the package with the given name, either as used in the config or as installed
+    #[must_use]
+    pub fn is_named(&self, name: &str) -> bool {
+        self.unresolved_name == name || self.name == name
+    }
+
+    /// Get the directory the plugin is installed to, inside of the data directory
+    ///
+    /// Optional plugins are installed to `opt`, and the others to `start`
+    #[must_use]
+    pub fn install_path(&self, data_path: &Path) -> PathBuf {
+        data_path
+            .join(if self.optional { "opt" } else { "start" })
+            .join(self.rename.as_ref().unwrap_or(&self.name))
+    }
+
+    /// Check out the plugin at its recorded inputs inside of the data directory, and apply its patches
+    ///
+    /// Nothing is resolved, so this only needs network access if the checkout is missing the recorded commit.
+    ///
+    /// # Errors
+    /// This function will return an error if the plugin cannot be loaded, or if a patch changed since the plugin was
+    /// resolved or does not apply
+    pub fn checkout(&self, smiths: &[Loaders], data_path: &Path) -> Result<(), LoadError> {
+        let smith = smiths
+            .iter()
+            .find(|s| s.name() == self.smith)
+            .ok_or_else(|| {
+                Report::new(LoadError)
+                    .attach_printable(format!("Failed to find smith. Smith name: {}", self.smith))
+            })?;
+
+        let package_path = self.install_path(data_path);
+
+        smith
+            .load(&self.loader_data, &package_path)
+            .attach_printable_lazy(|| {
+                format!(
+                    "Failed to load package. Package name: {}, Package path: {}",
+                    self.name,
+                    package_path.display()
+                )
+            })?;
+
+        for patch in &self.patches {
+            let contents = std::fs::read(&patch.path)
+                .into_report()
+                .attach_printable_lazy(|| {
+                    format!(
+                        "Failed to read patch. Package name: {}, Patch path: {}",
+                        self.name, patch.path
+                    )
+                })
+                .change_context(LoadError)?;
+
+            if Patch::digest(&contents) != patch.sha256 {
+                return Err(Report::new(LoadError).attach_printable(format!(
+                    "Patch changed while installing. Package name: {}, Patch path: {}",
+                    self.name, patch.path
+                )));
+            }
+
+            smith
+                .apply_patch(&package_path, &contents)
+                .attach_printable_lazy(|| {
+                    format!(
+                        "Failed to apply patch. Package name: {}, Patch path: {}",
+                        self.name, patch.path
+                    )
+                })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rkyv::to_bytes;
+
+    #[test]
+    fn test_generation_hash_serialize_deserialize() {
+        let generation = GenerationHash(ConfigHash::Sha256([7; 32]), 32);
+
+        let bytes = to_bytes::<_, 1024>(&generation).unwrap();
+        let deserialized = rkyv::from_bytes::<GenerationHash>(&bytes).unwrap();
+        assert_eq!(generation, deserialized);
+    }
+
+    #[test]
+    fn test_generations_file_serialize_deserialize() {
+        let mut generations_file = GenerationsFile::new();
+        let manifest = Manifest {
+            neovim_version: "0.5.0".to_string(),
+            plugins: vec![],
+            conditions: vec![],
+        };
+
+        let metadata = Metadata {
+            description: Some("bump telescope".to_string()),
+            ..Metadata::detect(Path::new("packages.json"))
+        };
+
+        let snapshot = ConfigSnapshot {
+            merged: "{}".to_string(),
+            sources: vec![ConfigSource {
+                path: "packages.json".to_string(),
+                contents: "{ \"packages\": {} }".to_string(),
+            }],
+        };
+
+        let hash = generations_file.add_to_generations(
+            ConfigHash::Sha256([1; 32]),
+            manifest,
+            metadata,
+            Some(snapshot.clone()),
+        );
+
+        let bytes = generations_file.to_bytes().unwrap();
+        assert_eq!(format_version(&bytes), Some(FORMAT_VERSION));
+        let deserialized = GenerationsFile::from_bytes(&bytes).unwrap();
+
+        let generations_file_manifest = generations_file.generations.get(&hash).unwrap();
+        let deserialized_manifest = deserialized.generations.get(&hash).unwrap();
+
+        assert_eq!(
+            generations_file_manifest.manifest.neovim_version,
+            deserialized_manifest.manifest.neovim_version
+        );
+        assert_eq!(
+            deserialized_manifest.metadata.description.as_deref(),
+            Some("bump telescope")
+        );
+        assert_eq!(
+            deserialized_manifest.metadata.alpacka_version.as_deref(),
+            Some(env!("CARGO_PKG_VERSION"))
+        );
+        assert_eq!(deserialized_manifest.config, Some(snapshot));
+    }
+
+    #[test]
+    fn test_get_latest_manifest() {
+        let mut generations_file = GenerationsFile::new();
+        let hash = ConfigHash::Sha256([0; 32]);
+
+        for neovim_version in ["0.8.0", "0.9.0"] {
+            generations_file.add_to_generations(
+                hash,
+                Manifest::new(neovim_version.to_string(), vec![], vec![]),
+                Metadata::default(),
+                None,
+            );
+        }
+
+        assert_eq!(
+            generations_file
+                .get_latest_manifest(hash)
+                .unwrap()
+                .neovim_version,
+            "0.9.0"
+        );
+    }
+
+    #[test]
+    fn test_generation_ids_are_global() {
+        let mut generations_file = GenerationsFile::new();
+        let first = ConfigHash::Sha256([2; 32]);
+        let second = ConfigHash::Sha256([1; 32]);
+
+        for hash in [first, second, first] {
+            generations_file.add_to_generations(
+                hash,
+                Manifest::new("0.9.0".to_string(), vec![], vec![]),
+                Metadata::default(),
+                None,
+            );
+        }
+
+        // the map sorts by config hash, but the timeline is in creation order
+        let timeline: Vec<_> = generations_file
+            .timeline()
+            .into_iter()
+            .map(|(hash, generation)| (*hash, generation.id))
+            .collect();
+
+        assert_eq!(
+            timeline,
+            vec![
+                (GenerationHash(first, 1), 1),
+                (GenerationHash(second, 1), 2),
+                (GenerationHash(first, 2), 3),
+            ]
+        );
+        assert_eq!(generations_file.get_next_id(), 4);
+        assert!(generations_file
+            .generations
+            .values()
+            .all(|generation| generation.created_at > 0));
+    }
+
+    #[test]
+    fn test_get_next_generation_number() {
+        let mut generations_file = GenerationsFile::new();
+        let manifest = Manifest {
+            neovim_version: "0.5.0".to_string(),
+            plugins: vec![],
+            conditions: vec![],
+        };
+
+        let hash = ConfigHash::Sha256([0; 32]);
+
+        assert_eq!(generations_file.get_next_generation_number(hash), 1);
+
+        generations_file.add_to_generations(hash, manifest, Metadata::default(), None);
+
+        assert_eq!(generations_file.get_next_generation_number(hash), 2);
+    }
+
+    #[test]
+    fn test_reusable_inputs() {
+        use crate::{package::Config as ConfigPackage, smith::GitInput};
+
+        let inputs = Inputs::Git(GitInput::new(
+            "0123456789abcdef0123456789abcdef01234567".to_string(),
+            "https://github.com/nvim-lua/plenary.nvim.git".to_string(),
+        ));
+        let manifest = Manifest::new(
+            "0.9.0".to_string(),
+            vec![Plugin {
+                name: "plenary.nvim".to_string(),
+                unresolved_name: "github:nvim-lua/plenary.nvim".to_string(),
+                rename: None,
+                optional: false,
+                dependencies: vec![],
+                smith: "git".to_string(),
+                build: String::new(),
+                loader_data: inputs,
+                overridden: None,
+                patches: Vec::new(),
+                resolved_from: Some(Resolution {
+                    source: "github:nvim-lua/plenary.nvim".to_string(),
+                    version: Some("tag:v0.1.4".to_string()),
+                }),
+            }],
+            vec![],
+        );
+
+        let package = |version: &str| ConfigPackage {
+            version: Some(version.to_string()),
+            ..ConfigPackage::default()
+        };
+        let with_smith = |config_package| WithSmith {
+            smith: "git".to_string(),
+            package: Package {
+                name: "github:nvim-lua/plenary.nvim",
+                source: "github:nvim-lua/plenary.nvim",
+                config_package,
+            },
+        };
+
+        let unchanged = package("tag:v0.1.4");
+        assert!(manifest.reusable_inputs(&with_smith(&unchanged)).is_some());
+
+        let changed = package("tag:v0.1.3");
+        assert!(manifest.reusable_inputs(&with_smith(&changed)).is_none());
+    }
+}
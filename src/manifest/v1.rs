@@ -0,0 +1,103 @@
+//! The first generations file format with a header, version 1
+//!
+//! Generations were stored as their manifests only, without an ID, creation time, metadata or config, and there was no
+//! active generation. Plugins did not record overrides, patches or what they were resolved from, like in the
+//! [`legacy`](super::legacy) format. This format is only read, to migrate old files.
+
+use super::{legacy::Plugin, GenerationHash, GenerationsFileError};
+use crate::condition::ConditionOutcome;
+use bytecheck::CheckBytes;
+use error_stack::{Report, Result};
+use rkyv::{check_archived_root, Archive, Deserialize, Infallible};
+use std::collections::BTreeMap;
+
+#[derive(Archive, rkyv::Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes))]
+struct Manifest {
+    neovim_version: String,
+    plugins: Vec<Plugin>,
+    conditions: Vec<ConditionOutcome>,
+}
+
+#[derive(Archive, rkyv::Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes))]
+struct GenerationsFile(BTreeMap<GenerationHash, Manifest>);
+
+/// Migrate the archive of a generations file with format version 1, without its header
+///
+/// Generations keep their hash, so generations migrated from the legacy format can still be adopted with
+/// [`GenerationsFile::adopt_legacy`](super::GenerationsFile::adopt_legacy)
+///
+/// # Panics
+/// Cannot panic, as the only error that can occur when deserializing is [`Infallible`]
+pub(super) fn migrate(archive: &[u8]) -> Result<super::GenerationsFile, GenerationsFileError> {
+    let archived = check_archived_root::<GenerationsFile>(archive).map_err(|error| {
+        Report::new(GenerationsFileError::Parse).attach_printable(error.to_string())
+    })?;
+
+    let GenerationsFile(generations) = archived.deserialize(&mut Infallible).unwrap();
+
+    Ok(super::GenerationsFile {
+        // the creation order of these generations is unknown, so they get IDs in the order they are stored in
+        generations: generations
+            .into_iter()
+            .zip(1..)
+            .map(|((hash, manifest), id)| {
+                (
+                    hash,
+                    super::Generation {
+                        id,
+                        created_at: 0,
+                        metadata: super::Metadata::default(),
+                        config: None,
+                        manifest: super::Manifest {
+                            neovim_version: manifest.neovim_version,
+                            plugins: manifest.plugins.into_iter().map(Plugin::migrate).collect(),
+                            conditions: manifest.conditions,
+                        },
+                    },
+                )
+            })
+            .collect(),
+        active: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::{format_version, ConfigHash, FORMAT_VERSION, HEADER_LENGTH, MAGIC};
+    use rkyv::to_bytes;
+
+    #[test]
+    fn test_migrate() {
+        let hash = GenerationHash(ConfigHash::Sha256([1; 32]), 1);
+        let v1 = GenerationsFile(BTreeMap::from([(
+            hash,
+            Manifest {
+                neovim_version: "0.9.0".to_string(),
+                plugins: vec![],
+                conditions: vec![ConditionOutcome {
+                    package: "github:nvim-lua/plenary.nvim".to_string(),
+                    check: "os = linux".to_string(),
+                    passed: true,
+                }],
+            },
+        )]));
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&1_u32.to_le_bytes());
+        bytes.resize(HEADER_LENGTH, 0);
+        bytes.extend_from_slice(&to_bytes::<_, 1024>(&v1).unwrap());
+
+        let generations = super::super::GenerationsFile::from_bytes(&bytes).unwrap();
+        let generation = &generations.generations[&hash];
+        assert_eq!((generation.id, generation.created_at), (1, 0));
+        assert_eq!(generation.manifest.conditions.len(), 1);
+        assert!(generation.config.is_none());
+
+        // migrated files are written in the current format
+        let bytes = generations.to_bytes().unwrap();
+        assert_eq!(format_version(&bytes), Some(FORMAT_VERSION));
+    }
+}
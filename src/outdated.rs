@@ -0,0 +1,132 @@
+//! A module which checks the plugins of a manifest against their remotes
+//!
+//! Plugins which follow a branch report how many commits they are behind, and plugins pinned to a version tag report
+//! the newer version tags. Both report which of them are breaking changes, as detected by [`crate::breaking`].
+//! Checking never changes the manifest, the generations file or the installed plugins.
+
+use crate::{
+    manifest::{Manifest, Plugin},
+    package::Package,
+    smith::enums::Loaders,
+};
+use error_stack::{IntoReport, Result, ResultExt};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fmt::Display;
+
+#[derive(Debug)]
+/// An error that can occur when checking plugins against their remotes
+pub struct OutdatedError;
+
+impl Display for OutdatedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Failed to check packages for updates")
+    }
+}
+
+impl error_stack::Context for OutdatedError {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+/// How a plugin compares to its remote
+pub enum Status {
+    /// The plugin is at the latest commit of its branch, or at the newest version tag
+    UpToDate,
+    /// The branch the plugin follows has new commits
+    Behind {
+        /// The number of new commits, or [`None`] if the recorded commit is no longer part of the branch
+        commits: Option<usize>,
+        /// The latest commit of the branch
+        latest: String,
+        /// The subjects of the new commits which are breaking changes
+        breaking: Vec<String>,
+    },
+    /// Tags with a newer version than the one the plugin is pinned to exist, oldest first
+    NewerTags {
+        tags: Vec<String>,
+        /// The newer tags which bump the major version
+        breaking: Vec<String>,
+    },
+    /// The plugin is pinned to a commit, or to a tag which is not a version, so it cannot be outdated
+    Pinned,
+}
+
+impl Status {
+    /// Check if a newer commit or version exists
+    #[must_use]
+    pub const fn is_outdated(&self) -> bool {
+        matches!(self, Self::Behind { .. } | Self::NewerTags { .. })
+    }
+
+    /// Check if updating would include a breaking change
+    #[must_use]
+    pub const fn is_breaking(&self) -> bool {
+        match self {
+            Self::Behind { breaking, .. } | Self::NewerTags { breaking, .. } => {
+                !breaking.is_empty()
+            }
+            Self::UpToDate | Self::Pinned => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// A plugin of a manifest, and how it compares to its remote
+pub struct Outdated {
+    /// The name of the package, as used in the config
+    pub name: String,
+    /// The version the plugin was resolved at, or [`None`] for the default branch
+    pub version: Option<String>,
+    /// The revision the plugin is at, such as a git commit hash
+    pub revision: String,
+    #[serde(flatten)]
+    pub status: Status,
+}
+
+/// Check a plugin against its remote
+fn check_plugin(plugin: &Plugin, smiths: &[Loaders]) -> Result<Outdated, OutdatedError> {
+    let outdated = |version, status| Outdated {
+        name: plugin.unresolved_name.clone(),
+        version,
+        revision: plugin.loader_data.revision().to_string(),
+        status,
+    };
+
+    // plugins which were not resolved from a config are pinned to the commit they were imported at
+    let Some(resolution) = &plugin.resolved_from else {
+        return Ok(outdated(None, Status::Pinned));
+    };
+
+    let smith = smiths
+        .iter()
+        .find(|smith| smith.name() == plugin.smith)
+        .ok_or(OutdatedError)
+        .into_report()
+        .attach_printable_lazy(|| format!("Smith {} not found", plugin.smith))?;
+
+    let config_package = resolution.to_config();
+    let package = Package {
+        name: &plugin.unresolved_name,
+        source: &resolution.source,
+        config_package: &config_package,
+    };
+
+    let status = smith
+        .check_outdated(&plugin.loader_data, &package)
+        .attach_printable_lazy(|| format!("Package name: {}", plugin.unresolved_name))
+        .change_context(OutdatedError)?;
+
+    Ok(outdated(resolution.version.clone(), status))
+}
+
+/// Check every plugin of a manifest against its remote, in manifest order
+///
+/// # Errors
+/// This function will return an error if the remote of a plugin cannot be reached
+pub fn check(manifest: &Manifest, smiths: &[Loaders]) -> Result<Vec<Outdated>, OutdatedError> {
+    manifest
+        .plugins
+        .par_iter()
+        .map(|plugin| check_plugin(plugin, smiths))
+        .collect()
+}
@@ -1,28 +1,186 @@
 //! A module which contains structs and types for packages
 
-use crate::smith::{
-    enums::{Inputs, Loaders},
-    ResolveError,
+use crate::{
+    condition::{Condition, ConditionContext},
+    config::{suggest, Config as FullConfig},
+    graph::DependencyGraph,
+    smith::{
+        enums::{Inputs, Loaders},
+        ResolveError,
+    },
 };
+use bytecheck::CheckBytes;
 use error_stack::{IntoReport, Result, ResultExt};
 use rayon::prelude::*;
-use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use rkyv::Archive;
+use schemars::JsonSchema;
+use serde::{de::Error as DeError, Deserialize, Deserializer, Serialize};
+use std::{collections::BTreeMap, path::PathBuf};
 
-#[derive(Debug, Deserialize, Serialize, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+/// The prefixes a package version can start with
+pub const VERSION_PREFIXES: [&str; 3] = ["tag", "commit", "branch"];
+
+#[derive(
+    Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Hash, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[serde(deny_unknown_fields)]
+#[schemars(rename = "PackageConfig")]
 /// A package declaration, as found in a config file
 pub struct Config {
     /// Don't load the package on startup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub optional: Option<bool>,
-    /// The package version. Internally uses git tags when using git, else the resolver decides
+    /// The package version, in the form `{prefix}:{value}`. The prefix is one of `tag`, `commit` or `branch`
+    #[serde(
+        default,
+        deserialize_with = "deserialize_version",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub version: Option<String>,
-    /// rename the package to something else
+    /// rename the package to something else. Environment variables and `~` are expanded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub rename: Option<String>,
-    /// A command to build the package. This is run in the package directory
+    /// A command to build the package. This is run in the package directory. Environment variables and `~` are expanded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub build: Option<String>,
     /// A list of dependencies
-    #[serde(default)]
-    pub dependencies: BTreeMap<String, Config>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependencies: BTreeMap<String, Self>,
+    /// Only load the package when every check in this condition passes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<Condition>,
+    /// Remove the package when merging configs. This is used by local overlays to disable packages from an included config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled: Option<bool>,
+    /// Patch files which are applied in order after the package is checked out, relative to the config file which
+    /// declares them. Environment variables and `~` are expanded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patches: Vec<PathBuf>,
+}
+
+impl Config {
+    /// Merge another package declaration on top of this one.
+    ///
+    /// Fields which are set in `other` take precedence, and dependencies are merged with [`merge_packages`].
+    /// Patches from `other` replace the existing ones, unless there are none.
+    pub fn merge(&mut self, other: Self) {
+        let Self {
+            optional,
+            version,
+            rename,
+            build,
+            dependencies,
+            when,
+            disabled: _,
+            patches,
+        } = other;
+
+        self.optional = optional.or(self.optional);
+        self.version = version.or_else(|| self.version.take());
+        self.rename = rename.or_else(|| self.rename.take());
+        self.build = build.or_else(|| self.build.take());
+        self.when = when.or_else(|| self.when.take());
+
+        if !patches.is_empty() {
+            self.patches = patches;
+        }
+
+        merge_packages(&mut self.dependencies, dependencies);
+    }
+
+    /// Check if the package's condition passes. Packages without a condition are always loaded
+    #[must_use]
+    pub fn should_load(&self, name: &str, context: &ConditionContext) -> bool {
+        self.when
+            .as_ref()
+            .is_none_or(|condition| condition.passes(name, context))
+    }
+}
+
+#[derive(
+    Debug,
+    Default,
+    Deserialize,
+    Serialize,
+    JsonSchema,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+#[archive_attr(derive(CheckBytes, Debug))]
+#[serde(deny_unknown_fields)]
+/// An override, which replaces the source or version of a package wherever it appears in the dependency tree
+///
+/// The package keeps its name and install directory, so plugins which depend on it load the replacement instead
+pub struct Override {
+    /// The package to resolve instead, e.g. a fork such as `github:me/plenary.nvim`, or a local checkout such as
+    /// `path:~/src/plenary.nvim`. Environment variables and `~` are expanded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// The version to use instead of the versions requested by the config and dependents, in the form `{prefix}:{value}`
+    #[serde(
+        default,
+        deserialize_with = "deserialize_version",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub version: Option<String>,
+}
+
+/// Merge a list of package declarations on top of another.
+///
+/// Packages which exist in both lists are merged with [`Config::merge`], and packages which are marked as `disabled`
+/// in the overlay are removed.
+pub fn merge_packages(base: &mut BTreeMap<String, Config>, overlay: BTreeMap<String, Config>) {
+    for (name, package) in overlay {
+        if package.disabled.unwrap_or(false) {
+            base.remove(&name);
+        } else {
+            base.entry(name).or_default().merge(package);
+        }
+    }
+}
+
+/// Check that a version is a known prefix followed by a non-empty value
+///
+/// # Errors
+/// Returns a description of the problem if the version is malformed, including a suggestion if one could be found
+pub fn validate_version(version: &str) -> std::result::Result<(), String> {
+    match version.split_once(':') {
+        Some((prefix, "")) if VERSION_PREFIXES.contains(&prefix) => Err(format!(
+            "invalid version `{version}`, the {prefix} after `{prefix}:` cannot be empty"
+        )),
+        Some((prefix, _)) if VERSION_PREFIXES.contains(&prefix) => Ok(()),
+        Some((prefix, value)) => Err(format!(
+            "invalid version `{version}`, unknown prefix `{prefix}`, expected one of `tag`, `commit`, `branch`{}",
+            suggest(prefix, VERSION_PREFIXES)
+                .map(|prefix| format!(". help: did you mean `{prefix}:{value}`?"))
+                .unwrap_or_default()
+        )),
+        None => Err(format!(
+            "invalid version `{version}`, versions must be prefixed with one of `tag:`, `commit:`, `branch:`. help: did you mean `tag:{version}`?"
+        )),
+    }
+}
+
+/// Deserialize a package version, rejecting malformed versions instead of falling back to the default branch
+pub(crate) fn deserialize_version<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let version = Option::<String>::deserialize(deserializer)?;
+
+    if let Some(version) = &version {
+        validate_version(version).map_err(D::Error::custom)?;
+    }
+
+    Ok(version)
 }
 
 #[derive(Debug, Clone)]
@@ -32,6 +190,8 @@ pub struct Config {
 pub struct Package<'a> {
     /// The name of the package
     pub name: &'a str,
+    /// The name the package is resolved from. This is the name of the package, unless an override replaces its source
+    pub source: &'a str,
     /// The package's config, as found in the config file
     pub config_package: &'a Config,
 }
@@ -48,6 +208,18 @@ pub struct WithSmith<'a> {
 /// A type alias for a package loader input and a package with a smith
 pub type WithLoaderInput<'a> = (Inputs, WithSmith<'a>);
 
+/// Find the declaration of a package inside of a dependency tree
+fn find_dependency<'a>(
+    dependencies: &'a BTreeMap<String, Config>,
+    name: &str,
+) -> Option<(&'a String, &'a Config)> {
+    dependencies.get_key_value(name).or_else(|| {
+        dependencies
+            .values()
+            .find_map(|dependency| find_dependency(&dependency.dependencies, name))
+    })
+}
+
 impl<'a> WithSmith<'a> {
     /// Check if this package is optional
     #[must_use]
@@ -75,69 +247,71 @@ impl<'a> WithSmith<'a> {
     /// This function will also resolve all dependencies of the package.
     ///
     /// See [`WithSmith::resolve`] for more information.
+    /// Dependencies whose condition does not pass are skipped, and a dependency which appears several times in the
+    /// tree is resolved once, as its first declaration.
     ///
     /// # Errors
-    /// This function will return an error if the package or one of its dependencies cannot be resolved.
+    /// This function will return an error if the dependency graph of the package is not valid, or if the package or
+    /// one of its dependencies cannot be resolved.
+    #[deprecated(
+        note = "use `DependencyGraph::new`, which merges packages requested several times into one"
+    )]
     #[tracing::instrument]
     pub fn resolve_recurse(
         self,
         smiths: &'a [Loaders],
-    ) -> Result<Vec<WithLoaderInput>, ResolveError> {
-        let mut deps = self
-            .package
-            .config_package
-            .dependencies
-            .par_iter()
-            .map(|(name, config_package)| {
-                let pkg = Package {
-                    name,
-                    config_package,
-                };
+        context: &ConditionContext,
+    ) -> Result<Vec<WithLoaderInput<'a>>, ResolveError> {
+        let config = FullConfig {
+            packages: BTreeMap::from([(
+                self.package.name.to_string(),
+                self.package.config_package.clone(),
+            )]),
+            ..FullConfig::default()
+        };
+
+        let graph = DependencyGraph::new(&config, smiths, context).change_context(ResolveError)?;
 
-                let smith_to_use = smiths
-                    .iter()
-                    .find(|s| s.get_package_name(pkg.name).is_some())
+        graph
+            .packages()
+            .into_par_iter()
+            .map(|node| {
+                let package = if node.package.name == self.package.name {
+                    self.package.clone()
+                } else {
+                    let (name, config_package) = find_dependency(
+                        &self.package.config_package.dependencies,
+                        node.package.name,
+                    )
                     .ok_or(ResolveError)
                     .into_report()
                     .attach_printable_lazy(|| {
-                        format!("Failed to find smith. Package name: {}", pkg.name)
+                        format!("Dependency not found. Package name: {}", node.package.name)
                     })?;
 
-                let package = Self {
-                    smith: smith_to_use.name(),
-                    package: pkg,
+                    Package {
+                        name,
+                        source: name,
+                        config_package,
+                    }
                 };
 
-                package.resolve_recurse(smiths)
-            })
-            .collect::<Result<Vec<_>, _>>()?
-            .into_iter()
-            .flatten()
-            .collect::<Vec<_>>();
+                let package = Self {
+                    smith: node.smith,
+                    package,
+                };
 
-        let smith_to_use = smiths
-            .iter()
-            .find(|s| s.name() == self.smith)
-            .ok_or(ResolveError)
-            .into_report()
-            .attach_printable_lazy(|| {
-                format!("Failed to find smith. Smith name: {}", self.smith)
-            })?;
-
-        let loader_data = smith_to_use
-            .resolve(&self.package)
-            .attach_printable_lazy(|| {
-                format!(
-                    "Failed to resolve package. Package name: {}",
-                    self.package.name
-                )
+                package
+                    .resolve(smiths)
+                    .attach_printable_lazy(|| {
+                        format!(
+                            "Failed to resolve package. Package name: {}",
+                            package.package.name
+                        )
+                    })
+                    .map(|loader_data| (loader_data, package))
             })
-            .change_context(ResolveError)?;
-
-        let mut final_package = vec![(loader_data, self)];
-        deps.append(&mut final_package);
-
-        Ok(deps)
+            .collect()
     }
 }
 
@@ -151,16 +325,32 @@ mod tests {
             smith: "test".to_string(),
             package: Package {
                 name: "test",
+                source: "test",
                 config_package: &Config {
                     optional: Some(true),
                     version: None,
                     rename: None,
                     build: None,
                     dependencies: BTreeMap::new(),
+                    when: None,
+                    disabled: None,
+                    patches: Vec::new(),
                 },
             },
         };
 
         assert!(with_smith.is_optional());
     }
+
+    #[test]
+    fn test_validate_version() {
+        assert!(validate_version("tag:v1.0").is_ok());
+        assert!(validate_version("branch:main").is_ok());
+
+        assert!(validate_version("v1.0").unwrap_err().contains("`tag:v1.0`"));
+        assert!(validate_version("tga:v1.0")
+            .unwrap_err()
+            .contains("`tag:v1.0`"));
+        assert!(validate_version("commit:").is_err());
+    }
 }
@@ -0,0 +1,178 @@
+//! A module which plans what installing a manifest changes in the data directory
+//!
+//! A plan compares the manifest which is installed, if any, with the manifest which would be installed. It only reads
+//! the data directory, to find plugins which have to be cloned, so it can be used to preview an install.
+
+use crate::manifest::{Manifest, Plugin};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+/// A change to the data directory
+pub enum Action {
+    /// The plugin is not in the data directory yet
+    Clone {
+        name: String,
+        revision: String,
+        path: PathBuf,
+    },
+    /// The plugin is checked out at another revision
+    /// `from` is [`None`] if the plugin is in the data directory, but the installed revision is not known
+    Update {
+        name: String,
+        from: Option<String>,
+        to: String,
+    },
+    /// The plugin is not in the new manifest anymore, or it moved between `start` and `opt` or was renamed, in which
+    /// case it is cloned again at its new path
+    Remove { name: String, path: PathBuf },
+    /// The build command of the plugin is run, which happens every time a plugin is installed
+    Build { name: String, command: String },
+}
+
+/// Find the plugin of a manifest with the same name in the config
+fn find<'a>(manifest: &'a Manifest, plugin: &Plugin) -> Option<&'a Plugin> {
+    manifest
+        .plugins
+        .iter()
+        .find(|other| other.unresolved_name == plugin.unresolved_name)
+}
+
+/// Get the plugins of the installed manifest whose paths the new manifest does not use, and their paths
+///
+/// These are left over after installing the new manifest, and should be removed
+#[must_use]
+pub fn stale_plugins<'a>(
+    installed: &'a Manifest,
+    manifest: &Manifest,
+    data_path: &Path,
+) -> Vec<(&'a Plugin, PathBuf)> {
+    let paths: Vec<_> = manifest
+        .plugins
+        .iter()
+        .map(|plugin| plugin.install_path(data_path))
+        .collect();
+
+    installed
+        .plugins
+        .iter()
+        .map(|plugin| (plugin, plugin.install_path(data_path)))
+        .filter(|(_, path)| path.exists() && !paths.contains(path))
+        .collect()
+}
+
+/// Plan installing a manifest over the installed one
+///
+/// Plugins are removed first, then cloned and updated, and built last, in manifest order
+#[must_use]
+pub fn plan(installed: Option<&Manifest>, manifest: &Manifest, data_path: &Path) -> Vec<Action> {
+    let removes = installed
+        .map(|installed| stale_plugins(installed, manifest, data_path))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(plugin, path)| Action::Remove {
+            name: plugin.unresolved_name.clone(),
+            path,
+        });
+
+    let mut checkouts = Vec::new();
+
+    for plugin in &manifest.plugins {
+        let path = plugin.install_path(data_path);
+        let revision = plugin.loader_data.revision().to_string();
+
+        if !path.exists() {
+            checkouts.push(Action::Clone {
+                name: plugin.unresolved_name.clone(),
+                revision,
+                path,
+            });
+            continue;
+        }
+
+        let from = installed
+            .and_then(|installed| find(installed, plugin))
+            .map(|old| old.loader_data.revision().to_string());
+        if from.as_ref() != Some(&revision) {
+            checkouts.push(Action::Update {
+                name: plugin.unresolved_name.clone(),
+                from,
+                to: revision,
+            });
+        }
+    }
+
+    let builds = manifest
+        .plugins
+        .iter()
+        .filter(|plugin| !plugin.build.is_empty())
+        .map(|plugin| Action::Build {
+            name: plugin.unresolved_name.clone(),
+            command: plugin.build.clone(),
+        });
+
+    removes.chain(checkouts).chain(builds).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::fixtures::{manifest, plugin};
+
+    #[test]
+    fn test_plan() {
+        let data_path = tempfile::tempdir().unwrap();
+        let data_path = data_path.path();
+
+        let installed = manifest(vec![
+            plugin("plenary.nvim", "aaaa"),
+            plugin("telescope.nvim", "bbbb"),
+            plugin("popup.nvim", "cccc"),
+        ]);
+        for plugin in &installed.plugins {
+            std::fs::create_dir_all(plugin.install_path(data_path)).unwrap();
+        }
+
+        let new = manifest(vec![
+            plugin("plenary.nvim", "aaaa"),
+            Plugin {
+                optional: true,
+                build: "make".to_string(),
+                ..plugin("telescope.nvim", "dddd")
+            },
+            plugin("lspconfig.nvim", "eeee"),
+        ]);
+
+        assert_eq!(
+            plan(Some(&installed), &new, data_path),
+            vec![
+                // a plugin which moved to `opt` is cloned again there
+                Action::Remove {
+                    name: "github:nvim-lua/telescope.nvim".to_string(),
+                    path: data_path.join("start/telescope.nvim"),
+                },
+                Action::Remove {
+                    name: "github:nvim-lua/popup.nvim".to_string(),
+                    path: data_path.join("start/popup.nvim"),
+                },
+                Action::Clone {
+                    name: "github:nvim-lua/telescope.nvim".to_string(),
+                    revision: "dddd".to_string(),
+                    path: data_path.join("opt/telescope.nvim"),
+                },
+                Action::Clone {
+                    name: "github:nvim-lua/lspconfig.nvim".to_string(),
+                    revision: "eeee".to_string(),
+                    path: data_path.join("start/lspconfig.nvim"),
+                },
+                Action::Build {
+                    name: "github:nvim-lua/telescope.nvim".to_string(),
+                    command: "make".to_string(),
+                },
+            ]
+        );
+
+        assert!(plan(Some(&installed), &installed, data_path).is_empty());
+    }
+}
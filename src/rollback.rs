@@ -0,0 +1,214 @@
+//! A module which rolls back to a previous generation
+//!
+//! Rolling back checks out every plugin of a generation at the inputs recorded in its manifest, so no package is
+//! resolved again. Plugins which the previously active generation installed, but the rolled back generation does not,
+//! are removed from the data directory. The generation is then marked active, so `alpacka install` keeps it.
+//!
+//! A generation of another config can be rolled back to by its ID. It is only kept by `alpacka install` while the
+//! config it was created from is in use, so that config has to be restored too.
+
+use crate::{
+    manifest::{ConfigHash, GenerationHash, GenerationsFile},
+    plan::stale_plugins,
+    smith::enums::Loaders,
+};
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use rayon::prelude::*;
+use std::{fmt::Display, path::Path};
+
+#[derive(Debug)]
+/// An error that can occur when rolling back
+pub enum RollbackError {
+    /// No generation has this ID
+    UnknownGeneration(u64),
+    /// The config has no generation before the active one
+    NoPreviousGeneration,
+    /// A plugin could not be checked out
+    Checkout,
+    /// A plugin of the previously active generation could not be removed
+    Remove,
+}
+
+impl Display for RollbackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownGeneration(generation) => {
+                write!(f, "Generation {generation} does not exist")
+            }
+            Self::NoPreviousGeneration => {
+                f.write_str("There is no generation before the active one to roll back to")
+            }
+            Self::Checkout => f.write_str("Failed to check out a plugin"),
+            Self::Remove => f.write_str("Failed to remove a plugin"),
+        }
+    }
+}
+
+impl error_stack::Context for RollbackError {}
+
+/// Get the generation of a config hash to roll back to
+///
+/// This is the generation with the ID `to` if it is given, which can belong to any config. Else it is the generation
+/// of the config before the active one, or before the latest one if the active generation belongs to another config.
+///
+/// # Errors
+/// This function will return an error if no generation has the ID `to`, or if the config has no generation before
+/// the current one
+pub fn target_generation(
+    generations: &GenerationsFile,
+    config_hash: ConfigHash,
+    to: Option<u64>,
+) -> Result<GenerationHash, RollbackError> {
+    if let Some(to) = to {
+        return generations
+            .get_by_id(to)
+            .map(|(hash, _)| *hash)
+            .ok_or_else(|| Report::new(RollbackError::UnknownGeneration(to)));
+    }
+
+    let current = generations
+        .active
+        .filter(|GenerationHash(hash, _)| *hash == config_hash)
+        .map(|GenerationHash(_, generation)| generation)
+        .or_else(|| generations.get_latest_generation_number(config_hash))
+        .ok_or_else(|| Report::new(RollbackError::NoPreviousGeneration))?;
+
+    generations
+        .get_previous(GenerationHash(config_hash, current))
+        .ok_or_else(|| Report::new(RollbackError::NoPreviousGeneration))
+}
+
+/// Roll back to a generation of a config hash, and mark it active
+///
+/// See [`target_generation`] for which generation is picked. Returns the generation which is now active. The
+/// generations file is only changed in memory, so it has to be saved by the caller.
+///
+/// # Errors
+/// This function will return an error if there is no generation to roll back to, if a plugin cannot be checked out,
+/// or if a plugin of the previously active generation cannot be removed
+pub fn rollback(
+    generations: &mut GenerationsFile,
+    config_hash: ConfigHash,
+    to: Option<u64>,
+    smiths: &[Loaders],
+    data_path: &Path,
+) -> Result<GenerationHash, RollbackError> {
+    let target = target_generation(generations, config_hash, to)?;
+    let manifest = &generations.generations[&target].manifest;
+
+    manifest
+        .plugins
+        .par_iter()
+        .map(|plugin| {
+            plugin
+                .checkout(smiths, data_path)
+                .attach_printable_lazy(|| format!("Package name: {}", plugin.unresolved_name))
+                .change_context(RollbackError::Checkout)
+        })
+        .collect::<Result<(), _>>()?;
+
+    let stale = generations
+        .active
+        .and_then(|active| generations.generations.get(&active))
+        .map(|active| stale_plugins(&active.manifest, manifest, data_path))
+        .unwrap_or_default();
+
+    for (_, path) in stale {
+        std::fs::remove_dir_all(&path)
+            .into_report()
+            .attach_printable_lazy(|| format!("Plugin path: {}", path.display()))
+            .change_context(RollbackError::Remove)?;
+    }
+
+    generations.active = Some(target);
+
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::{Manifest, Metadata};
+
+    /// Create a generation of another config, followed by `count` generations of a config, so that their IDs are one
+    /// higher than their numbers
+    fn generations(count: usize) -> (GenerationsFile, ConfigHash) {
+        let mut generations = GenerationsFile::new();
+        let config_hash = ConfigHash::Sha256([1; 32]);
+
+        for config_hash in std::iter::once(ConfigHash::Sha256([2; 32]))
+            .chain(std::iter::repeat_n(config_hash, count))
+        {
+            generations.add_to_generations(
+                config_hash,
+                Manifest::new("0.9.0".to_string(), vec![], vec![]),
+                Metadata::default(),
+                None,
+            );
+        }
+
+        (generations, config_hash)
+    }
+
+    #[test]
+    fn test_target_generation() {
+        let (mut generations, config_hash) = generations(3);
+
+        // without an active generation, the one before the latest is picked
+        assert_eq!(
+            target_generation(&generations, config_hash, None).unwrap(),
+            GenerationHash(config_hash, 2)
+        );
+
+        generations.active = Some(GenerationHash(config_hash, 2));
+        assert_eq!(
+            target_generation(&generations, config_hash, None).unwrap(),
+            GenerationHash(config_hash, 1)
+        );
+        assert_eq!(
+            target_generation(&generations, config_hash, Some(4)).unwrap(),
+            GenerationHash(config_hash, 3)
+        );
+
+        // generations of other configs can be picked by ID
+        assert_eq!(
+            target_generation(&generations, config_hash, Some(1)).unwrap(),
+            GenerationHash(ConfigHash::Sha256([2; 32]), 1)
+        );
+
+        // an active generation of another config is ignored
+        generations.active = Some(GenerationHash(ConfigHash::Sha256([2; 32]), 1));
+        assert_eq!(
+            target_generation(&generations, config_hash, None).unwrap(),
+            GenerationHash(config_hash, 2)
+        );
+    }
+
+    #[test]
+    fn test_target_generation_errors() {
+        let (mut generations, config_hash) = generations(1);
+
+        let error = target_generation(&generations, config_hash, Some(4)).unwrap_err();
+        assert!(matches!(
+            error.current_context(),
+            RollbackError::UnknownGeneration(4)
+        ));
+
+        generations.active = Some(GenerationHash(config_hash, 1));
+        let error = target_generation(&generations, config_hash, None).unwrap_err();
+        assert!(matches!(
+            error.current_context(),
+            RollbackError::NoPreviousGeneration
+        ));
+    }
+
+    #[test]
+    fn test_rollback_marks_the_generation_active() {
+        let (mut generations, config_hash) = generations(2);
+
+        let target = rollback(&mut generations, config_hash, None, &[], Path::new(".")).unwrap();
+
+        assert_eq!(target, GenerationHash(config_hash, 1));
+        assert_eq!(generations.active, Some(target));
+    }
+}
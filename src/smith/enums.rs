@@ -4,7 +4,9 @@ use bytecheck::CheckBytes;
 use error_stack::Result;
 use rkyv::{Archive, Deserialize, Serialize};
 
-use super::{git::Input, Git, LoadError, ResolveError, Smith};
+use crate::outdated::Status;
+
+use super::{git::Input, Commit, Git, LoadError, ResolveError, Smith};
 
 #[derive(Debug)]
 pub enum Loaders {
@@ -26,6 +28,13 @@ impl Loaders {
         }
     }
 
+    #[must_use]
+    pub fn package_name_pattern(&self) -> String {
+        match self {
+            Self::Git(git) => git.package_name_pattern(),
+        }
+    }
+
     /// Resolve the input for a package
     ///
     /// # Errors
@@ -36,6 +45,57 @@ impl Loaders {
         })
     }
 
+    /// Check if a resolved package is behind its remote
+    ///
+    /// # Errors
+    /// This function will return an error if the remote cannot be reached.
+    pub fn check_outdated(
+        &self,
+        input: &Inputs,
+        package: &super::Package,
+    ) -> Result<Status, ResolveError> {
+        match input {
+            Inputs::Git(input) => match self {
+                Self::Git(git) => git.check_outdated(input, package),
+            },
+        }
+    }
+
+    /// Get the commits after `from` up to and including `to`, from the package loaded at `path`
+    ///
+    /// # Errors
+    /// This function will return an error if the changes cannot be found.
+    pub fn get_change_log(
+        &self,
+        from: Option<&Inputs>,
+        to: &Inputs,
+        path: &Path,
+    ) -> Result<Vec<Commit>, LoadError> {
+        match (self, to) {
+            (Self::Git(git), Inputs::Git(to)) => {
+                git.get_change_log(from.map(|Inputs::Git(from)| from), to, path)
+            }
+        }
+    }
+
+    /// Get the version tags after `from` up to and including `to` which bump the major version, from the package
+    /// loaded at `path`
+    ///
+    /// # Errors
+    /// This function will return an error if the changes cannot be found.
+    pub fn get_breaking_tags(
+        &self,
+        from: &Inputs,
+        to: &Inputs,
+        path: &Path,
+    ) -> Result<Vec<String>, LoadError> {
+        match (self, from, to) {
+            (Self::Git(git), Inputs::Git(from), Inputs::Git(to)) => {
+                git.get_breaking_tags(from, to, path)
+            }
+        }
+    }
+
     /// Load a package
     ///
     /// # Errors
@@ -47,6 +107,16 @@ impl Loaders {
             },
         }
     }
+
+    /// Apply a patch to a loaded package
+    ///
+    /// # Errors
+    /// This function will return an error if the patch does not apply.
+    pub fn apply_patch(&self, package_path: &Path, patch: &[u8]) -> Result<(), LoadError> {
+        match self {
+            Self::Git(git) => git.apply_patch(package_path, patch),
+        }
+    }
 }
 
 #[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
@@ -54,3 +124,13 @@ impl Loaders {
 pub enum Inputs {
     Git(Input),
 }
+
+impl Inputs {
+    /// The revision the input is locked to, such as a git commit hash
+    #[must_use]
+    pub fn revision(&self) -> &str {
+        match self {
+            Self::Git(input) => input.commit_hash(),
+        }
+    }
+}
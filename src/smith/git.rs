@@ -1,13 +1,17 @@
-use crate::package::Package;
+use crate::{
+    breaking::{is_breaking_commit, is_major_bump, tag_version},
+    outdated::Status,
+    package::Package,
+};
 use bytecheck::CheckBytes;
-use error_stack::{Context, IntoReport, Result as ErrorStackResult, ResultExt};
-use git2::{ErrorCode, Repository};
+use error_stack::{Context, IntoReport, Report, Result as ErrorStackResult, ResultExt};
+use git2::{ApplyLocation, Diff, ErrorCode, Repository};
 use rkyv::Archive;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Display, path::Path};
+use std::{fmt::Display, iter::once, path::Path};
 use tracing::debug;
 
-use super::{LoadError, LoaderInput, ResolveError, Smith};
+use super::{Commit, LoadError, LoaderInput, ResolveError, Smith};
 
 #[derive(Debug)]
 /// An error that can occur when resolving a git package
@@ -58,6 +62,57 @@ impl Git {
     pub const fn new_with_type(clone_type: CloneType) -> Self {
         Self { clone_type }
     }
+
+    /// Get the URL of the remote repository for a package name, using the clone type of this smith
+    ///
+    /// # Errors
+    /// This function will return an error if the package name is not a git package name
+    pub fn remote_url(&self, name: &str) -> ErrorStackResult<String, ResolveError> {
+        let (repo_type, repo_url) = name
+            .split_once(':')
+            .ok_or(ResolveError)
+            .into_report()
+            .attach_printable_lazy(|| format!("Not a git package: {name}"))?;
+
+        let url = match (repo_type, repo_url) {
+            ("git", repo) => match self.clone_type {
+                CloneType::Ssh => format!("git@{repo}.git"),
+                CloneType::Https => {
+                    let (host, path) = repo
+                        .split_once(':')
+                        .ok_or(ResolveError)
+                        .into_report()
+                        .attach_printable_lazy(|| {
+                            format!(
+                                "Failed to parse git repo: {repo}. Format: git:{{host}}:{{path}}"
+                            )
+                        })?;
+
+                    format!("https://{host}/{path}.git")
+                }
+            },
+            ("github", repo_url) => match self.clone_type {
+                CloneType::Ssh => format!("git@github.com:{repo_url}.git"),
+                CloneType::Https => format!("https://github.com/{repo_url}.git"),
+            },
+            ("gitlab", repo_url) => match self.clone_type {
+                CloneType::Ssh => format!("git@gitlab.com:{repo_url}.git"),
+                CloneType::Https => format!("https://gitlab.com/{repo_url}.git"),
+            },
+            ("srht", repo_url) => match self.clone_type {
+                CloneType::Ssh => format!("git@git.sr.ht:{repo_url}"),
+                CloneType::Https => format!("https://git.sr.ht/{repo_url}"),
+            },
+            // a local checkout, which is fetched from directly
+            ("path", path) => path.to_string(),
+            _ => {
+                return Err(Report::new(ResolveError)
+                    .attach_printable(format!("Not a git package: {name}")))
+            }
+        };
+
+        Ok(url)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
@@ -84,6 +139,29 @@ pub struct Input {
     remote: String,
 }
 
+impl Input {
+    #[must_use]
+    /// Create a new git loader input, for a commit which is already known
+    pub const fn new(commit_hash: String, remote: String) -> Self {
+        Self {
+            commit_hash,
+            remote,
+        }
+    }
+
+    #[must_use]
+    /// The commit hash to lock to
+    pub fn commit_hash(&self) -> &str {
+        &self.commit_hash
+    }
+
+    #[must_use]
+    /// The URL of the remote repository
+    pub fn remote(&self) -> &str {
+        &self.remote
+    }
+}
+
 impl LoaderInput for Input {}
 
 impl Smith for Git {
@@ -95,100 +173,15 @@ impl Smith for Git {
 
     #[tracing::instrument]
     fn resolve(&self, package: &Package) -> ErrorStackResult<Self::Input, ResolveError> {
-        let Some((repo_type, repo_url)) = package
-                .name
-                .split_once(':') else {
-                    unreachable!("should be handled by handles_package")
-                };
-
-        let repo_type = repo_type.to_owned();
-        let repo_url = repo_url.to_owned();
-
-        let url = match (repo_type.as_str(), repo_url.as_str()) {
-            ("git", repo) => match self.clone_type {
-                CloneType::Ssh => format!("git@{repo}.git"),
-                CloneType::Https => {
-                    let (host, path) = repo
-                        .split_once(':')
-                        .ok_or(ResolveError)
-                        .into_report()
-                        .attach_printable_lazy(|| {
-                            format!(
-                                "Failed to parse git repo: {repo}. Format: git:{{host}}:{{path}}"
-                            )
-                        })?;
-
-                    format!("https://{host}/{path}.git")
-                }
-            },
-            ("github", repo_url) => match self.clone_type {
-                CloneType::Ssh => format!("git@github.com:{repo_url}.git"),
-                CloneType::Https => format!("https://github.com/{repo_url}.git"),
-            },
-            ("gitlab", repo_url) => match self.clone_type {
-                CloneType::Ssh => format!("git@gitlab.com:{repo_url}.git"),
-                CloneType::Https => format!("https://gitlab.com/{repo_url}.git"),
-            },
-            ("srht", repo_url) => match self.clone_type {
-                CloneType::Ssh => format!("git@git.sr.ht:{repo_url}"),
-                CloneType::Https => format!("https://git.sr.ht/{repo_url}"),
-            },
-            _ => unreachable!("should be handled by handles_package"),
-        };
+        let url = self.remote_url(package.source)?;
 
         debug!("url: {url}");
 
-        let lock_type = match package
-            .config_package
-            .version
-            .as_ref()
-            .and_then(|v| v.split_once(':'))
-        {
-            Some(("tag", tag)) => LockType::Tag(tag.to_string()),
-            Some(("commit", commit)) => LockType::Commit(commit.to_string()),
-            Some(("branch", branch)) => LockType::Branch(branch.to_string()),
-            _ => LockType::Default,
-        };
+        let lock_type = lock_type(package)?;
 
         debug!("lock_type: {lock_type:?}");
 
-        let temp_git_dir = tempfile::tempdir()
-            .into_report()
-            .change_context(GitError::IoError)
-            .attach_printable_lazy(|| format!("Failed to create temp dir for git repo: {url}"))
-            .change_context(ResolveError)?;
-
-        // init git repo and add remote
-        let repo = git2::Repository::init(temp_git_dir.path())
-            .into_report()
-            .change_context(GitError::GitError)
-            .attach_printable_lazy(|| format!("Failed to init git repo: {url}"))
-            .change_context(ResolveError)?;
-
-        let mut remote = repo
-            .remote_anonymous(&url)
-            .into_report()
-            .change_context(GitError::GitError)
-            .attach_printable_lazy(|| format!("Failed to add remote: {url}"))
-            .change_context(ResolveError)?;
-
-        fetch_remote(&url, &lock_type, &mut remote).change_context(ResolveError)?;
-
-        let fetch_head = repo
-            .find_reference("FETCH_HEAD")
-            .into_report()
-            .change_context(GitError::GitError)
-            .attach_printable_lazy(|| format!("Failed to find FETCH_HEAD: {url}. Check if the specified commit, tag, or branch exists."))
-            .change_context(ResolveError)?;
-
-        let commit_hash = fetch_head
-            .peel_to_commit()
-            .into_report()
-            .change_context(GitError::GitError)
-            .attach_printable_lazy(|| format!("Failed to peel FETCH_HEAD to commit: {url}"))
-            .change_context(ResolveError)?
-            .id()
-            .to_string();
+        let commit_hash = with_fetched(&url, &lock_type, |_, head| Ok(head.to_string()))?;
 
         Ok(Input {
             commit_hash,
@@ -196,37 +189,99 @@ impl Smith for Git {
         })
     }
 
-    /// Gets the commit messages between a sha and the current HEAD, if not sha is provided, it
-    /// takes up to 5 of the latest commits on the current branch, provided they exist.
+    /// Branches are fetched into a temporary repository to count the new commits, and tags are listed without
+    /// fetching anything
+    #[tracing::instrument]
+    fn check_outdated(
+        &self,
+        input: &Self::Input,
+        package: &Package,
+    ) -> ErrorStackResult<Status, ResolveError> {
+        match lock_type(package)? {
+            LockType::Commit(_) => Ok(Status::Pinned),
+            LockType::Tag(tag) => {
+                let Some(current) = tag_version(&tag) else {
+                    return Ok(Status::Pinned);
+                };
+
+                let mut newer = list_tags(&input.remote)?
+                    .into_iter()
+                    .filter_map(|tag| tag_version(&tag).map(|version| (version, tag)))
+                    .filter(|(version, _)| *version > current)
+                    .collect::<Vec<_>>();
+                newer.sort();
+
+                Ok(if newer.is_empty() {
+                    Status::UpToDate
+                } else {
+                    Status::NewerTags {
+                        breaking: newer
+                            .iter()
+                            .filter(|(version, _)| is_major_bump(&current, version))
+                            .map(|(_, tag)| tag.clone())
+                            .collect(),
+                        tags: newer.into_iter().map(|(_, tag)| tag).collect(),
+                    }
+                })
+            }
+            lock_type @ (LockType::Branch(_) | LockType::Default) => {
+                with_fetched(&input.remote, &lock_type, |repo, head| {
+                    if head.to_string() == input.commit_hash {
+                        return Ok(Status::UpToDate);
+                    }
+
+                    let messages = new_commit_messages(repo, &input.commit_hash, head);
+
+                    Ok(Status::Behind {
+                        commits: messages.as_ref().map(Vec::len),
+                        latest: head.to_string(),
+                        breaking: messages
+                            .unwrap_or_default()
+                            .iter()
+                            .filter(|message| is_breaking_commit(message))
+                            .filter_map(|message| message.lines().next())
+                            .map(ToString::to_string)
+                            .collect(),
+                    })
+                })
+            }
+        }
+    }
+
+    /// Gets the commits after `from` up to and including `to`, newest first. If `from` is not provided, it
+    /// takes up to 5 of the latest commits up to `to`, provided they exist.
+    ///
+    /// The checkout at `path` is only read. If it does not exist or is missing any of the commits, the remote is
+    /// fetched into a temporary repository instead, so checkouts are never changed by looking at a changelog.
     ///
     /// ```ignore
     /// use alpacka::{package::{Package, Config}, smith::{Smith, Git}};
     /// use std::path::Path;
-    /// use std::collections::BTreeMap;
     ///
     /// let curr_dir = Path::new("testing");
     ///
     /// let smith = Git::new();
+    /// let package = |version: &str| Config {
+    ///     version: Some(version.to_string()),
+    ///     ..Config::default()
+    /// };
     ///
-    /// let pkg = smith.resolve(&Package {
-    ///     name: "github:zackartz/testing_repo".to_string(),
-    ///     config_package: Config {
-    ///         version: Some("tag:0.1.1".to_string()),
-    ///         build: None,
-    ///         dependencies: BTreeMap::new(),
-    ///         optional: None,
-    ///         rename: None,
-    ///     }
+    /// let old = smith.resolve(&Package {
+    ///     name: "github:zackartz/testing_repo",
+    ///     source: "github:zackartz/testing_repo",
+    ///     config_package: &package("tag:0.1.0"),
+    /// }).unwrap();
+    /// let new = smith.resolve(&Package {
+    ///     name: "github:zackartz/testing_repo",
+    ///     source: "github:zackartz/testing_repo",
+    ///     config_package: &package("tag:0.1.1"),
     /// }).unwrap();
     ///
-    /// smith.load(&pkg, &curr_dir);
-    ///
-    /// // sha of 0.1.0
-    /// let oid = git2::Oid::from_str("90600fc317747afad28add17705199fc1eead17c").unwrap();
+    /// smith.load(&new, &curr_dir);
     ///
-    /// let commits = smith.get_change_log(Some(oid), &curr_dir).unwrap();
+    /// let commits = smith.get_change_log(Some(&old), &new, &curr_dir).unwrap();
     ///
-    /// assert_eq!(commits[0], "Update README.md");
+    /// assert_eq!(commits[0].subject, "Update README.md");
     ///
     /// // cleanup
     /// std::fs::remove_dir_all(&curr_dir);
@@ -234,19 +289,18 @@ impl Smith for Git {
     #[tracing::instrument]
     fn get_change_log(
         &self,
-        old_sha: Option<git2::Oid>,
+        from: Option<&Self::Input>,
+        to: &Self::Input,
         path: &Path,
-    ) -> ErrorStackResult<Vec<String>, LoadError> {
-        let repo = match git2::Repository::open(path) {
-            Ok(repo) => repo,
-            Err(e) => {
-                return Err(e)
-                    .into_report()
-                    .change_context(GitError::GitError)
-                    .attach_printable_lazy(|| format!("Failed to open repo: {path:?}"))
-                    .change_context(LoadError)
-            }
-        };
+    ) -> ErrorStackResult<Vec<Commit>, LoadError> {
+        let to_commit = parse_commit(&to.commit_hash)?;
+        let from_commit = from
+            .map(|from| parse_commit(&from.commit_hash))
+            .transpose()?;
+        let commits = once(to_commit).chain(from_commit).collect::<Vec<_>>();
+
+        let source = CommitSource::open(path, &to.remote, &commits)?;
+        let repo = &source.repo;
 
         let mut revwalk = repo
             .revwalk()
@@ -262,70 +316,115 @@ impl Smith for Git {
             .attach_printable_lazy(|| "Failed to set revwalk sorting".to_string())
             .change_context(LoadError)?;
 
-        let head = repo
-            .head()
-            .into_report()
-            .change_context(GitError::GitError)
-            .attach_printable_lazy(|| {
-                format!("Failed to get current HEAD for repository: {path:?}")
-            })
-            .change_context(LoadError)?;
-
-        let branch = head
-            .peel_to_commit()
+        revwalk
+            .push(to_commit)
             .into_report()
             .change_context(GitError::GitError)
             .attach_printable_lazy(|| {
-                format!("Failed to get current branch for repository: {path:?}")
+                format!("Failed to push commit to revwalk: {}", path.display())
             })
             .change_context(LoadError)?;
 
-        revwalk
-            .push(branch.id())
-            .into_report()
-            .change_context(GitError::GitError)
-            .attach_printable_lazy(|| format!("Failed to push commit to revwalk: {path:?}"))
-            .change_context(LoadError)?;
-
-        if let Some(sha) = old_sha {
-            let commit = repo
-                .find_commit(sha)
-                .into_report()
-                .change_context(GitError::GitError)
-                .attach_printable_lazy(|| "Failed to get commit".to_string())
-                .change_context(LoadError)?;
-
+        if let Some(from_commit) = from_commit {
             revwalk
-                .hide(commit.id())
+                .hide(from_commit)
                 .into_report()
                 .change_context(GitError::GitError)
-                .attach_printable_lazy(|| format!("Failed to push commit to revwalk {path:?}"))
+                .attach_printable_lazy(|| {
+                    format!("Failed to hide commit from revwalk {}", path.display())
+                })
                 .change_context(LoadError)?;
         }
 
-        let mut messages = vec![];
-        let mut count = 0;
-        for id in revwalk.take(5) {
-            let commit_id = id
-                .into_report()
-                .change_context(GitError::GitError)
-                .attach_printable_lazy(|| "Failed to get commit_id".to_string())
-                .change_context(LoadError)?;
+        let limit = if from.is_some() { usize::MAX } else { 5 };
 
-            let commit = repo
-                .find_commit(commit_id)
-                .into_report()
-                .change_context(GitError::GitError)
-                .attach_printable_lazy(|| format!("Failed to find commit {commit_id}"))
-                .change_context(LoadError)?;
-            messages.push(commit.message().unwrap_or("").to_string());
-            count += 1;
-            if old_sha.is_none() && count == 5 {
-                break;
-            }
-        }
+        revwalk
+            .take(limit)
+            .map(|id| {
+                let commit_id = id
+                    .into_report()
+                    .change_context(GitError::GitError)
+                    .attach_printable_lazy(|| "Failed to get commit_id".to_string())
+                    .change_context(LoadError)?;
+
+                let commit = repo
+                    .find_commit(commit_id)
+                    .into_report()
+                    .change_context(GitError::GitError)
+                    .attach_printable_lazy(|| format!("Failed to find commit {commit_id}"))
+                    .change_context(LoadError)?;
+
+                let author = commit.author().name().unwrap_or_default().to_string();
+
+                Ok(Commit {
+                    id: commit_id.to_string(),
+                    subject: commit.summary().unwrap_or_default().to_string(),
+                    message: commit.message().unwrap_or_default().trim().to_string(),
+                    author,
+                    time: commit.time().seconds(),
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument]
+    fn get_breaking_tags(
+        &self,
+        from: &Self::Input,
+        to: &Self::Input,
+        path: &Path,
+    ) -> ErrorStackResult<Vec<String>, LoadError> {
+        let from_commit = parse_commit(&from.commit_hash)?;
+        let to_commit = parse_commit(&to.commit_hash)?;
+
+        let source = CommitSource::open(path, &to.remote, &[to_commit, from_commit])?;
+        let repo = &source.repo;
+
+        let is_ancestor = |ancestor: git2::Oid, commit: git2::Oid| {
+            ancestor == commit || repo.graph_descendant_of(commit, ancestor).unwrap_or(false)
+        };
+
+        let tags = repo
+            .tag_names(None)
+            .into_report()
+            .change_context(GitError::GitError)
+            .attach_printable_lazy(|| format!("Failed to list tags: {}", to.remote))
+            .change_context(LoadError)?
+            .iter()
+            .flatten()
+            .filter_map(|tag| {
+                let commit = repo
+                    .revparse_single(&format!("refs/tags/{tag}"))
+                    .and_then(|object| object.peel_to_commit())
+                    .ok()?;
+
+                tag_version(tag).map(|version| (version, tag.to_string(), commit.id()))
+            })
+            .collect::<Vec<_>>();
+
+        let Some(current) = tags
+            .iter()
+            .filter(|(_, _, commit)| is_ancestor(*commit, from_commit))
+            .map(|(version, _, _)| version)
+            .max()
+        else {
+            return Ok(Vec::new());
+        };
+
+        let mut breaking = tags
+            .iter()
+            .filter(|(version, _, commit)| {
+                is_major_bump(current, version)
+                    && is_ancestor(*commit, to_commit)
+                    && !is_ancestor(*commit, from_commit)
+            })
+            .collect::<Vec<_>>();
+        breaking.sort();
 
-        Ok(messages.iter().map(|m| m.trim().to_string()).collect())
+        Ok(breaking
+            .into_iter()
+            .map(|(_, tag, _)| tag.clone())
+            .collect())
     }
 
     #[tracing::instrument]
@@ -348,17 +447,10 @@ impl Smith for Git {
             },
         };
 
-        repo.remote_anonymous(&input.remote)
-            .into_report()
-            .change_context(GitError::GitError)
-            .attach_printable_lazy(|| format!("Failed to add remote: {}", input.remote))
-            .change_context(LoadError)?;
+        let commit_hash = parse_commit(&input.commit_hash)?;
 
-        let commit_hash = git2::Oid::from_str(&input.commit_hash)
-            .into_report()
-            .change_context(GitError::GitError)
-            .attach_printable_lazy(|| format!("Failed to parse commit hash: {}", input.commit_hash))
-            .change_context(LoadError)?;
+        // an existing checkout does not have commits which were pushed after it was cloned, such as updates
+        fetch_missing(&repo, &input.remote, &[commit_hash])?;
 
         let commit = repo
             .find_commit(commit_hash)
@@ -378,15 +470,246 @@ impl Smith for Git {
         Ok(())
     }
 
+    /// Apply a patch to the index and the working directory.
+    ///
+    /// Files created by the patch are added to the index, so resetting to the pinned commit on the next load removes them.
+    #[tracing::instrument(skip(patch))]
+    fn apply_patch(&self, package_path: &Path, patch: &[u8]) -> ErrorStackResult<(), LoadError> {
+        let repo = Repository::open(package_path)
+            .into_report()
+            .change_context(GitError::GitError)
+            .attach_printable_lazy(|| format!("Failed to open repo: {}", package_path.display()))
+            .change_context(LoadError)?;
+
+        let diff = Diff::from_buffer(patch)
+            .into_report()
+            .change_context(GitError::GitError)
+            .attach_printable("Failed to parse patch")
+            .change_context(LoadError)?;
+
+        repo.apply(&diff, ApplyLocation::Both, None)
+            .into_report()
+            .change_context(GitError::GitError)
+            .attach_printable_lazy(|| format!("Patch does not apply to {}", package_path.display()))
+            .change_context(LoadError)
+    }
+
     fn get_package_name(&self, name: &str) -> Option<String> {
         match name.split_once(':') {
             Some(("git", name)) => name.rsplit_once('/').map(|(_, name)| name.to_string()),
             Some(("github", name)) => name.split_once('/').map(|(_, name)| name.to_string()),
             Some(("gitlab", name)) => name.split_once('/').map(|(_, name)| name.to_string()),
             Some(("srht", name)) => name.split_once('/').map(|(_, name)| name.to_string()),
+            Some(("path", path)) => Path::new(path)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string()),
             _ => None,
         }
     }
+
+    fn package_name_pattern(&self) -> String {
+        "^(git:[^:]+:.+/[^/]+|(github|gitlab|srht):[^/]+/.+|path:.+)$".to_string()
+    }
+}
+
+/// Parse a recorded commit hash
+fn parse_commit(commit: &str) -> ErrorStackResult<git2::Oid, LoadError> {
+    git2::Oid::from_str(commit)
+        .into_report()
+        .change_context(GitError::GitError)
+        .attach_printable_lazy(|| format!("Failed to parse commit hash: {commit}"))
+        .change_context(LoadError)
+}
+
+/// Fetch the branches and tags of a remote into a checkout, if it is missing any of the given commits
+fn fetch_missing(
+    repo: &Repository,
+    url: &str,
+    commits: &[git2::Oid],
+) -> ErrorStackResult<(), LoadError> {
+    if commits
+        .iter()
+        .all(|commit| repo.find_commit(*commit).is_ok())
+    {
+        return Ok(());
+    }
+
+    debug!("Fetching {url} to find commits {commits:?}");
+
+    repo.remote_anonymous(url)
+        .and_then(|mut remote| {
+            remote.fetch(
+                &[
+                    "+refs/heads/*:refs/remotes/origin/*",
+                    "+refs/tags/*:refs/tags/*",
+                ],
+                None,
+                None,
+            )
+        })
+        .into_report()
+        .change_context(GitError::GitError)
+        .attach_printable_lazy(|| format!("Failed to fetch: {url}"))
+        .change_context(LoadError)
+}
+
+/// A repository to read commits from, which is deleted when dropped if it is temporary
+struct CommitSource {
+    repo: Repository,
+    // declared after the repository, so it is dropped after it
+    _temp_git_dir: Option<tempfile::TempDir>,
+}
+
+impl CommitSource {
+    /// Open the checkout at `path` if it has all the given commits, else fetch them from `url` into a temporary
+    /// repository, so the checkout is never changed
+    fn open(path: &Path, url: &str, commits: &[git2::Oid]) -> ErrorStackResult<Self, LoadError> {
+        if let Some(repo) = Repository::open(path).ok().filter(|repo| {
+            commits
+                .iter()
+                .all(|commit| repo.find_commit(*commit).is_ok())
+        }) {
+            return Ok(Self {
+                repo,
+                _temp_git_dir: None,
+            });
+        }
+
+        let temp_git_dir = tempfile::tempdir()
+            .into_report()
+            .change_context(GitError::IoError)
+            .attach_printable_lazy(|| format!("Failed to create temp dir for git repo: {url}"))
+            .change_context(LoadError)?;
+
+        let repo = Repository::init_bare(temp_git_dir.path())
+            .into_report()
+            .change_context(GitError::GitError)
+            .attach_printable_lazy(|| format!("Failed to init git repo: {url}"))
+            .change_context(LoadError)?;
+
+        fetch_missing(&repo, url, commits)?;
+
+        Ok(Self {
+            repo,
+            _temp_git_dir: Some(temp_git_dir),
+        })
+    }
+}
+
+/// Get how a package is locked from its version
+fn lock_type(package: &Package) -> ErrorStackResult<LockType, ResolveError> {
+    match package
+        .config_package
+        .version
+        .as_ref()
+        .map(|v| v.split_once(':'))
+    {
+        Some(Some(("tag", tag))) => Ok(LockType::Tag(tag.to_string())),
+        Some(Some(("commit", commit))) => Ok(LockType::Commit(commit.to_string())),
+        Some(Some(("branch", branch))) => Ok(LockType::Branch(branch.to_string())),
+        None => Ok(LockType::Default),
+        Some(_) => Err(Report::new(ResolveError).attach_printable(format!(
+            "Invalid version for {}: {:?}. Format: {{tag,commit,branch}}:{{value}}",
+            package.name, package.config_package.version
+        ))),
+    }
+}
+
+/// Fetch a remote into a temporary repository, and run `f` with the repository and the fetched commit
+///
+/// The repository is deleted once `f` returns
+fn with_fetched<T>(
+    url: &String,
+    lock_type: &LockType,
+    f: impl FnOnce(&Repository, git2::Oid) -> ErrorStackResult<T, ResolveError>,
+) -> ErrorStackResult<T, ResolveError> {
+    let temp_git_dir = tempfile::tempdir()
+        .into_report()
+        .change_context(GitError::IoError)
+        .attach_printable_lazy(|| format!("Failed to create temp dir for git repo: {url}"))
+        .change_context(ResolveError)?;
+
+    // init git repo and add remote
+    let repo = git2::Repository::init(temp_git_dir.path())
+        .into_report()
+        .change_context(GitError::GitError)
+        .attach_printable_lazy(|| format!("Failed to init git repo: {url}"))
+        .change_context(ResolveError)?;
+
+    let mut remote = repo
+        .remote_anonymous(url)
+        .into_report()
+        .change_context(GitError::GitError)
+        .attach_printable_lazy(|| format!("Failed to add remote: {url}"))
+        .change_context(ResolveError)?;
+
+    fetch_remote(url, lock_type, &mut remote).change_context(ResolveError)?;
+
+    let fetch_head = repo
+        .find_reference("FETCH_HEAD")
+        .into_report()
+        .change_context(GitError::GitError)
+        .attach_printable_lazy(|| format!("Failed to find FETCH_HEAD: {url}. Check if the specified commit, tag, or branch exists."))
+        .change_context(ResolveError)?;
+
+    let head = fetch_head
+        .peel_to_commit()
+        .into_report()
+        .change_context(GitError::GitError)
+        .attach_printable_lazy(|| format!("Failed to peel FETCH_HEAD to commit: {url}"))
+        .change_context(ResolveError)?
+        .id();
+
+    f(&repo, head)
+}
+
+/// Get the messages of the commits between a commit and the head of a branch, newest first
+///
+/// Returns [`None`] if the commit is no longer part of the branch's history, e.g. after a force push
+fn new_commit_messages(repo: &Repository, commit: &str, head: git2::Oid) -> Option<Vec<String>> {
+    let commit = git2::Oid::from_str(commit).ok()?;
+    let mut revwalk = repo.revwalk().ok()?;
+
+    revwalk.push(head).ok()?;
+    revwalk.hide(commit).ok()?;
+
+    revwalk
+        .map(|id| {
+            let commit = repo.find_commit(id.ok()?).ok()?;
+            Some(commit.message().unwrap_or_default().to_string())
+        })
+        .collect()
+}
+
+/// List the tags of a remote repository, without fetching them
+fn list_tags(url: &str) -> ErrorStackResult<Vec<String>, ResolveError> {
+    let mut remote = git2::Remote::create_detached(url)
+        .into_report()
+        .change_context(GitError::GitError)
+        .attach_printable_lazy(|| format!("Failed to create remote: {url}"))
+        .change_context(ResolveError)?;
+
+    remote
+        .connect(git2::Direction::Fetch)
+        .into_report()
+        .change_context(GitError::GitError)
+        .attach_printable_lazy(|| format!("Failed to connect to remote: {url}"))
+        .change_context(ResolveError)?;
+
+    let heads = remote
+        .list()
+        .into_report()
+        .change_context(GitError::GitError)
+        .attach_printable_lazy(|| format!("Failed to list remote refs: {url}"))
+        .change_context(ResolveError)?;
+
+    Ok(heads
+        .iter()
+        .filter_map(|head| head.name().strip_prefix("refs/tags/"))
+        // annotated tags are listed twice, once peeled to the commit
+        .filter(|tag| !tag.ends_with("^{}"))
+        .map(ToString::to_string)
+        .collect())
 }
 
 /// Fetches the remote repository
@@ -434,3 +757,196 @@ fn fetch_remote(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::fixtures::commit;
+
+    #[test]
+    fn test_apply_patch() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+
+        std::fs::write(dir.path().join("init.lua"), "return 1\n").unwrap();
+
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("init.lua")).unwrap();
+        index.write().unwrap();
+
+        let patch = b"diff --git a/init.lua b/init.lua\n--- a/init.lua\n+++ b/init.lua\n@@ -1 +1 @@\n-return 1\n+return 2\n";
+
+        Git::new().apply_patch(dir.path(), patch).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("init.lua")).unwrap(),
+            "return 2\n"
+        );
+
+        // the patch no longer applies once it has been applied
+        assert!(Git::new().apply_patch(dir.path(), patch).is_err());
+    }
+
+    /// Commit a file to a repository, returning the new commit
+    #[test]
+    fn test_load_fetches_new_commits() {
+        let remote_dir = tempfile::tempdir().unwrap();
+        let remote = Repository::init(remote_dir.path()).unwrap();
+        let checkout = tempfile::tempdir().unwrap();
+        let checkout_path = checkout.path().join("plugin");
+        let url = remote_dir.path().display().to_string();
+
+        let first = commit(&remote, "return 1\n");
+        Git::new()
+            .load(&Input::new(first.to_string(), url.clone()), &checkout_path)
+            .unwrap();
+
+        let second = commit(&remote, "return 2\n");
+        Git::new()
+            .load(&Input::new(second.to_string(), url), &checkout_path)
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(checkout_path.join("init.lua")).unwrap(),
+            "return 2\n"
+        );
+    }
+
+    #[test]
+    fn test_get_change_log_walks_the_full_range() {
+        let remote_dir = tempfile::tempdir().unwrap();
+        let remote = Repository::init(remote_dir.path()).unwrap();
+        let checkout = tempfile::tempdir().unwrap();
+        let url = remote_dir.path().display().to_string();
+
+        let commits = (1..=7)
+            .map(|n| commit(&remote, &format!("return {n}\n")))
+            .collect::<Vec<_>>();
+        let from = Input::new(commits[0].to_string(), url.clone());
+        let to = Input::new(commits[6].to_string(), url);
+
+        Git::new().load(&to, checkout.path()).unwrap();
+
+        let change_log = Git::new()
+            .get_change_log(Some(&from), &to, checkout.path())
+            .unwrap();
+        assert_eq!(change_log.len(), 6);
+        assert_eq!(change_log[0].subject, "return 7");
+        assert_eq!(change_log[0].author, "alpacka");
+
+        let latest = Git::new()
+            .get_change_log(None, &to, checkout.path())
+            .unwrap();
+        assert_eq!(latest.len(), 5);
+    }
+
+    #[test]
+    fn test_get_change_log_without_checkout() {
+        let remote_dir = tempfile::tempdir().unwrap();
+        let remote = Repository::init(remote_dir.path()).unwrap();
+        let checkout = tempfile::tempdir().unwrap();
+        let url = remote_dir.path().display().to_string();
+
+        let first = commit(&remote, "return 1\n");
+        let from = Input::new(first.to_string(), url.clone());
+        Git::new().load(&from, checkout.path()).unwrap();
+
+        let second = commit(&remote, "return 2\n");
+        let to = Input::new(second.to_string(), url);
+
+        let missing = checkout.path().join("missing");
+        let change_log = Git::new()
+            .get_change_log(Some(&from), &to, &missing)
+            .unwrap();
+        assert_eq!(change_log.len(), 1);
+        assert!(!missing.exists());
+
+        // an outdated checkout is not fetched into
+        let change_log = Git::new()
+            .get_change_log(Some(&from), &to, checkout.path())
+            .unwrap();
+        assert_eq!(change_log[0].subject, "return 2");
+        assert!(Repository::open(checkout.path())
+            .unwrap()
+            .find_commit(second)
+            .is_err());
+    }
+
+    #[test]
+    fn test_get_breaking_tags() {
+        let remote_dir = tempfile::tempdir().unwrap();
+        let remote = Repository::init(remote_dir.path()).unwrap();
+        let url = remote_dir.path().display().to_string();
+        let missing = remote_dir.path().join("missing");
+
+        let commits = (1..=4)
+            .map(|n| commit(&remote, &format!("return {n}\n")))
+            .collect::<Vec<_>>();
+        let input = |n: usize| Input::new(commits[n].to_string(), url.clone());
+
+        let breaking = |from: usize, to: usize| {
+            Git::new()
+                .get_breaking_tags(&input(from), &input(to), &missing)
+                .unwrap()
+        };
+
+        // there is no version to compare to
+        assert!(breaking(0, 3).is_empty());
+
+        for (n, tag) in [(0, "v1.0.0"), (1, "v1.1.0"), (2, "v2.0.0")] {
+            remote
+                .tag_lightweight(tag, &remote.find_object(commits[n], None).unwrap(), false)
+                .unwrap();
+        }
+
+        assert_eq!(breaking(0, 3), ["v2.0.0"]);
+        assert_eq!(breaking(0, 1), Vec::<String>::new());
+        assert_eq!(breaking(2, 3), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_check_outdated() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+
+        let first = commit(&repo, "return 1\n");
+        repo.tag_lightweight("v0.1.0", &repo.find_object(first, None).unwrap(), false)
+            .unwrap();
+        let second = commit(&repo, "return 2\n");
+        repo.tag_lightweight("v0.2.0", &repo.find_object(second, None).unwrap(), false)
+            .unwrap();
+        commit(&repo, "return 3\n");
+
+        let source = format!("path:{}", dir.path().display());
+        let input = Input::new(first.to_string(), dir.path().display().to_string());
+        let check = |version: String| {
+            Git::new().check_outdated(
+                &input,
+                &Package {
+                    name: &source,
+                    source: &source,
+                    config_package: &crate::package::Config {
+                        version: Some(version),
+                        ..Default::default()
+                    },
+                },
+            )
+        };
+
+        let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+        assert!(matches!(
+            check(format!("branch:{branch}")).unwrap(),
+            Status::Behind {
+                commits: Some(2),
+                ..
+            }
+        ));
+        assert_eq!(
+            check("tag:v0.1.0".to_string()).unwrap(),
+            Status::NewerTags {
+                tags: vec!["v0.2.0".to_string()],
+                breaking: vec!["v0.2.0".to_string()],
+            }
+        );
+        assert_eq!(check(format!("commit:{first}")).unwrap(), Status::Pinned);
+    }
+}
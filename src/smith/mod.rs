@@ -1,13 +1,14 @@
 pub mod enums;
 mod git;
-pub use git::Git;
+pub use git::{Git, Input as GitInput};
 use std::{
     fmt::{Debug as FmtDebug, Display},
     path::Path,
 };
 
-use crate::package::Package;
+use crate::{outdated::Status, package::Package};
 use error_stack::{Context, Result as ErrorStackResult};
+use serde::Serialize;
 
 #[derive(Debug)]
 /// An error that can occur when resolving a package
@@ -21,6 +22,21 @@ impl Display for ResolveError {
 
 impl Context for ResolveError {}
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// A commit in the history of a package
+pub struct Commit {
+    /// The commit hash
+    pub id: String,
+    /// The first line of the commit message
+    pub subject: String,
+    /// The full commit message
+    pub message: String,
+    /// The name of the commit's author
+    pub author: String,
+    /// When the commit was made, in seconds since the Unix epoch
+    pub time: i64,
+}
+
 #[derive(Debug)]
 /// An error that can occur when loading a package
 pub struct LoadError;
@@ -52,6 +68,10 @@ pub trait Smith: FmtDebug + Send + Sync {
     /// This is used to find the correct smith for a package
     fn get_package_name(&self, name: &str) -> Option<String>;
 
+    /// A regex matching every package name this smith can load.
+    /// This is used in the config JSON schema
+    fn package_name_pattern(&self) -> String;
+
     /// Resolve a package to a loader package, which has all the necessary information to load the package.
     /// This is cached inside of the generation file.
     ///
@@ -59,13 +79,39 @@ pub trait Smith: FmtDebug + Send + Sync {
     /// This function will return an error if the package cannot be resolved.
     fn resolve(&self, package: &Package) -> ErrorStackResult<Self::Input, ResolveError>;
 
-    /// Get latest commits for a git repo.
+    /// Check if a resolved package is behind its remote, without changing anything on disk.
+    /// `package` is the declaration the input was resolved from.
+    ///
+    /// # Errors
+    /// This function will return an error if the remote cannot be reached.
+    fn check_outdated(
+        &self,
+        input: &Self::Input,
+        package: &Package,
+    ) -> ErrorStackResult<Status, ResolveError>;
+
+    /// Get the commits after `from` up to and including `to`, newest first, from the package loaded at `path`, or from
+    /// its source if it is not loaded there. If `from` is [`None`], up to 5 of the latest commits are returned.
     ///
     /// # Errors
     /// This function will return an error if it cannot find the changes.
     fn get_change_log(
         &self,
-        old_sha: Option<git2::Oid>,
+        from: Option<&Self::Input>,
+        to: &Self::Input,
+        path: &Path,
+    ) -> ErrorStackResult<Vec<Commit>, LoadError>;
+
+    /// Get the version tags after `from` up to and including `to` which are a breaking change from the latest version
+    /// tag at or before `from`, oldest first, from the package loaded at `path`, or from its source if it is not loaded
+    /// there. If no version tag is at or before `from`, no tags are breaking.
+    ///
+    /// # Errors
+    /// This function will return an error if it cannot find the changes.
+    fn get_breaking_tags(
+        &self,
+        from: &Self::Input,
+        to: &Self::Input,
         path: &Path,
     ) -> ErrorStackResult<Vec<String>, LoadError>;
 
@@ -75,4 +121,11 @@ pub trait Smith: FmtDebug + Send + Sync {
     /// # Errors
     /// This function will return an error if the package cannot be loaded.
     fn load(&self, input: &Self::Input, package_path: &Path) -> ErrorStackResult<(), LoadError>;
+
+    /// Apply a patch to a loaded package.
+    /// This is called after [`Smith::load`], once for every patch of the package, in order.
+    ///
+    /// # Errors
+    /// This function will return an error if the patch is invalid or does not apply.
+    fn apply_patch(&self, package_path: &Path, patch: &[u8]) -> ErrorStackResult<(), LoadError>;
 }
@@ -0,0 +1,352 @@
+//! A module which updates the floating packages of a manifest
+//!
+//! A package is floating when it follows a branch, or the default branch, instead of being pinned to a tag or commit.
+//! Its recorded commit only moves forward when it is resolved again, which creates a new generation under the same
+//! config hash. Pinned packages are never resolved again, as they would resolve to the same commit.
+//!
+//! The new commits and version tags of a plugin are checked for breaking changes with [`crate::breaking`]. Updates
+//! with breaking changes are held back, and keep their old commit, unless they are accepted.
+
+use crate::{
+    breaking::is_breaking_commit,
+    manifest::{Manifest, Plugin},
+    package::{Package, WithSmith},
+    smith::enums::{Inputs, Loaders},
+};
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::{fmt::Display, path::Path};
+
+#[derive(Debug)]
+/// An error that can occur when updating packages
+pub enum UpdateError {
+    /// The package is not in the manifest
+    UnknownPackage(String),
+    /// The package is pinned to a tag or commit, so it cannot be updated
+    Pinned(String),
+    /// A package could not be resolved
+    Resolve,
+    /// The new commits of a package could not be read
+    Changelog,
+}
+
+impl Display for UpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownPackage(name) => write!(f, "Package {name} is not installed"),
+            Self::Pinned(name) => write!(
+                f,
+                "Package {name} is pinned to a tag or commit, change its version to update it"
+            ),
+            Self::Resolve => f.write_str("Failed to resolve packages to update"),
+            Self::Changelog => f.write_str("Failed to read the new commits of a package"),
+        }
+    }
+}
+
+impl error_stack::Context for UpdateError {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// A plugin whose revision changed on its remote
+pub struct Update {
+    /// The name of the package, as used in the config
+    pub name: String,
+    /// The revision the plugin was at, such as a git commit hash
+    pub old: String,
+    /// The revision the plugin is at after updating
+    pub new: String,
+    /// The subjects of the new commits which are breaking changes, followed by the new tags which bump the major version
+    pub breaking: Vec<String>,
+    /// The update has breaking changes which were not accepted, so the plugin stays at the old revision
+    pub held_back: bool,
+}
+
+/// Check if a plugin is floating, which means it was resolved from a config and follows a branch
+#[must_use]
+pub fn is_floating(plugin: &Plugin) -> bool {
+    plugin.resolved_from.as_ref().is_some_and(|resolution| {
+        resolution
+            .version
+            .as_ref()
+            .is_none_or(|version| version.starts_with("branch:"))
+    })
+}
+
+/// Resolve a plugin again, from the source and version it was resolved from
+fn resolve(plugin: &Plugin, smiths: &[Loaders]) -> Result<Option<Inputs>, UpdateError> {
+    let Some(resolution) = &plugin.resolved_from else {
+        return Ok(None);
+    };
+
+    let config_package = resolution.to_config();
+
+    WithSmith {
+        smith: plugin.smith.clone(),
+        package: Package {
+            name: &plugin.unresolved_name,
+            source: &resolution.source,
+            config_package: &config_package,
+        },
+    }
+    .resolve(smiths)
+    .attach_printable_lazy(|| format!("Package name: {}", plugin.unresolved_name))
+    .change_context(UpdateError::Resolve)
+    .map(Some)
+}
+
+/// Get the subjects of the breaking commits and the major version tags between two revisions of a plugin
+///
+/// The commits are read from the checkout of the plugin in `data_path`, or from its remote if it is not installed
+fn breaking_changes(
+    plugin: &Plugin,
+    inputs: &Inputs,
+    smiths: &[Loaders],
+    data_path: &Path,
+) -> Result<Vec<String>, UpdateError> {
+    let install_path = plugin.install_path(data_path);
+
+    let smith = smiths
+        .iter()
+        .find(|smith| smith.name() == plugin.smith)
+        .ok_or(UpdateError::Changelog)
+        .into_report()
+        .attach_printable_lazy(|| format!("Smith {} not found", plugin.smith))?;
+
+    let commits = smith
+        .get_change_log(Some(&plugin.loader_data), inputs, &install_path)
+        .attach_printable_lazy(|| format!("Package name: {}", plugin.unresolved_name))
+        .change_context(UpdateError::Changelog)?;
+
+    let tags = smith
+        .get_breaking_tags(&plugin.loader_data, inputs, &install_path)
+        .attach_printable_lazy(|| format!("Package name: {}", plugin.unresolved_name))
+        .change_context(UpdateError::Changelog)?;
+
+    Ok(commits
+        .into_iter()
+        .filter(|commit| is_breaking_commit(&commit.message))
+        .map(|commit| commit.subject)
+        .chain(tags)
+        .collect())
+}
+
+/// Resolve the floating packages of a manifest again, and record their new revisions
+///
+/// Every floating package is updated if `names` is empty, else only the named ones are. Updates with breaking
+/// changes are held back unless `accept_breaking` is set. Returns the updated manifest and the plugins whose revision
+/// changed, in manifest order. The manifest is unchanged if every update in that list is held back.
+///
+/// # Errors
+/// This function will return an error if a named package is not in the manifest or is pinned, or if a package cannot
+/// be resolved
+pub fn update(
+    mut manifest: Manifest,
+    smiths: &[Loaders],
+    names: &[String],
+    data_path: &Path,
+    accept_breaking: bool,
+) -> Result<(Manifest, Vec<Update>), UpdateError> {
+    for name in names {
+        let plugin = manifest
+            .plugins
+            .iter()
+            .find(|plugin| plugin.is_named(name))
+            .ok_or_else(|| Report::new(UpdateError::UnknownPackage(name.clone())))?;
+
+        if !is_floating(plugin) {
+            return Err(Report::new(UpdateError::Pinned(name.clone())));
+        }
+    }
+
+    let updates = manifest
+        .plugins
+        .par_iter_mut()
+        .filter(|plugin| {
+            is_floating(plugin)
+                && (names.is_empty() || names.iter().any(|name| plugin.is_named(name)))
+        })
+        .map(|plugin| {
+            let Some(inputs) = resolve(plugin, smiths)? else {
+                return Ok(None);
+            };
+
+            if inputs.revision() == plugin.loader_data.revision() {
+                return Ok(None);
+            }
+
+            let breaking = breaking_changes(plugin, &inputs, smiths, data_path)?;
+            let held_back = !breaking.is_empty() && !accept_breaking;
+
+            let update = Update {
+                name: plugin.unresolved_name.clone(),
+                old: plugin.loader_data.revision().to_string(),
+                new: inputs.revision().to_string(),
+                breaking,
+                held_back,
+            };
+
+            if !held_back {
+                plugin.loader_data = inputs;
+            }
+
+            Ok(Some(update))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((manifest, updates.into_iter().flatten().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        manifest::{fixtures, Resolution},
+        smith::{Git, GitInput},
+    };
+    use git2::Repository;
+
+    fn plugin(version: Option<&str>) -> Plugin {
+        Plugin {
+            resolved_from: Some(Resolution {
+                source: "github:nvim-lua/plenary.nvim".to_string(),
+                version: version.map(ToString::to_string),
+            }),
+            ..fixtures::plugin("plenary.nvim", "0123456789abcdef0123456789abcdef01234567")
+        }
+    }
+
+    #[test]
+    fn test_is_floating() {
+        assert!(is_floating(&plugin(None)));
+        assert!(is_floating(&plugin(Some("branch:master"))));
+        assert!(!is_floating(&plugin(Some("tag:v0.1.4"))));
+        assert!(!is_floating(&plugin(Some(
+            "commit:0123456789abcdef0123456789abcdef01234567"
+        ))));
+        assert!(!is_floating(&Plugin {
+            resolved_from: None,
+            ..plugin(None)
+        }));
+    }
+
+    #[test]
+    fn test_update_rejects_pinned_and_unknown_packages() {
+        let manifest = || {
+            Manifest::new(
+                "0.9.0".to_string(),
+                vec![plugin(Some("tag:v0.1.4"))],
+                vec![],
+            )
+        };
+
+        let error = update(
+            manifest(),
+            &[],
+            &["plenary.nvim".to_string()],
+            Path::new("."),
+            false,
+        )
+        .unwrap_err();
+        assert!(matches!(error.current_context(), UpdateError::Pinned(_)));
+
+        let error = update(
+            manifest(),
+            &[],
+            &["telescope.nvim".to_string()],
+            Path::new("."),
+            false,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error.current_context(),
+            UpdateError::UnknownPackage(_)
+        ));
+
+        // pinned packages are skipped when updating everything, so nothing is resolved
+        let (_, updates) = update(manifest(), &[], &[], Path::new("."), false).unwrap();
+        assert!(updates.is_empty());
+    }
+
+    #[test]
+    fn test_update_holds_back_major_version_tags() {
+        let remote_dir = tempfile::tempdir().unwrap();
+        let remote = Repository::init(remote_dir.path()).unwrap();
+        let data_dir = tempfile::tempdir().unwrap();
+        let url = remote_dir.path().display().to_string();
+
+        let first = fixtures::commit(&remote, "return 1\n");
+        let second = fixtures::commit(&remote, "return 2\n");
+        for (commit, tag) in [(first, "v1.0.0"), (second, "v2.0.0")] {
+            remote
+                .tag_lightweight(tag, &remote.find_object(commit, None).unwrap(), false)
+                .unwrap();
+        }
+
+        // the new commit is not marked as breaking, only its tag is
+        let manifest = || {
+            fixtures::manifest(vec![Plugin {
+                resolved_from: Some(Resolution {
+                    source: format!("path:{url}"),
+                    version: None,
+                }),
+                loader_data: Inputs::Git(GitInput::new(first.to_string(), url.clone())),
+                ..plugin(None)
+            }])
+        };
+        let smiths = [Loaders::Git(Git::new())];
+
+        let (held, updates) = update(manifest(), &smiths, &[], data_dir.path(), false).unwrap();
+        assert_eq!(updates.len(), 1);
+        assert!(updates[0].held_back);
+        assert_eq!(updates[0].breaking, ["v2.0.0"]);
+        assert_eq!(held.plugins[0].loader_data.revision(), first.to_string());
+
+        let (accepted, updates) = update(manifest(), &smiths, &[], data_dir.path(), true).unwrap();
+        assert!(!updates[0].held_back);
+        assert_eq!(
+            accepted.plugins[0].loader_data.revision(),
+            second.to_string()
+        );
+    }
+
+    #[test]
+    fn test_update_does_not_fetch_into_checkouts() {
+        let remote_dir = tempfile::tempdir().unwrap();
+        let remote = Repository::init(remote_dir.path()).unwrap();
+        let data_dir = tempfile::tempdir().unwrap();
+        let url = remote_dir.path().display().to_string();
+
+        let first = fixtures::commit(&remote, "return 1\n");
+        let installed = Plugin {
+            resolved_from: Some(Resolution {
+                source: format!("path:{url}"),
+                version: None,
+            }),
+            loader_data: Inputs::Git(GitInput::new(first.to_string(), url)),
+            ..plugin(None)
+        };
+        let smiths = [Loaders::Git(Git::new())];
+        let install_path = installed.install_path(data_dir.path());
+        smiths[0]
+            .load(&installed.loader_data, &install_path)
+            .unwrap();
+
+        let second = fixtures::commit(&remote, "return 2\n");
+
+        // the new commits are read from a temporary repository, so a dry run leaves the checkout as it was
+        let (_, updates) = update(
+            fixtures::manifest(vec![installed]),
+            &smiths,
+            &[],
+            data_dir.path(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(updates[0].new, second.to_string());
+        assert!(Repository::open(&install_path)
+            .unwrap()
+            .find_commit(second)
+            .is_err());
+    }
+}